@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use hoo_api_types::{
+    Group, GroupAction, GroupAttributes, GroupCollection, GroupNumber, Light, LightCollection, LightNumber,
+    LightSelector, LightState, SavedScene,
+};
+
+use crate::error::{HueError, Result};
+use crate::response::StateChangeResult;
+use crate::watcher::LightWatcher;
+use crate::write_queue::{self, WriteQueue};
+
+/// Reads and changes lights and groups. `HueClient` does this with a Hue bridge, but the server and CLI only need
+/// this much, so they can just as well be driven by a simulated backend, one that records and replays, or one for
+/// lights that aren't Hue at all. Everything past the group methods is built on the ones before it.
+#[async_trait]
+pub trait LightBackend: Clone + Send + Sync + 'static {
+    async fn get_all_lights(&self) -> Result<LightCollection>;
+
+    async fn get_light(&self, light_num: LightNumber) -> Result<Light>;
+
+    async fn set_state(&self, light_num: LightNumber, state: &LightState) -> Result<StateChangeResult>;
+
+    async fn get_all_groups(&self) -> Result<GroupCollection>;
+
+    async fn get_group(&self, group_num: GroupNumber) -> Result<Group>;
+
+    async fn create_group(&self, attributes: &GroupAttributes) -> Result<GroupNumber>;
+
+    async fn update_group(&self, group_num: GroupNumber, attributes: &GroupAttributes) -> Result<StateChangeResult>;
+
+    async fn delete_group(&self, group_num: GroupNumber) -> Result<StateChangeResult>;
+
+    async fn set_group_action(&self, group_num: GroupNumber, action: &GroupAction) -> Result<StateChangeResult>;
+
+    /// Sets several lights. Results are in the same order as `states`, and one light failing doesn't stop the
+    /// others. Sets them one at a time unless a backend knows how to do better.
+    async fn set_states(&self, states: &[(LightNumber, LightState)]) -> Vec<(LightNumber, Result<StateChangeResult>)> {
+        let mut results = Vec::with_capacity(states.len());
+        for (light_num, state) in states {
+            results.push((*light_num, self.set_state(*light_num, state).await));
+        }
+        results
+    }
+
+    /// Lights that are on and reachable
+    async fn get_active_lights(&self) -> Result<LightCollection> {
+        let active_lights = self
+            .get_all_lights()
+            .await?
+            .into_iter()
+            .filter(|(_, l)| l.state.is_on() && l.state.is_reachable())
+            .collect();
+
+        Ok(active_lights)
+    }
+
+    /// The lights a selector matches. Only asks for groups if the selector needs them.
+    async fn select_lights(&self, selector: &LightSelector) -> Result<LightCollection> {
+        let mut lights = self.get_all_lights().await?;
+        let groups = if selector.needs_groups() {
+            self.get_all_groups().await?
+        } else {
+            GroupCollection::new()
+        };
+
+        let selected = selector.resolve(&lights, &groups);
+        lights.retain(|light_num, _| selected.contains(light_num));
+        Ok(lights)
+    }
+
+    async fn on(&self, light_num: LightNumber) -> Result<StateChangeResult> {
+        self.set_state(light_num, &LightState::new().on(true)).await
+    }
+
+    async fn off(&self, light_num: LightNumber) -> Result<StateChangeResult> {
+        self.set_state(light_num, &LightState::new().on(false)).await
+    }
+
+    async fn toggle(&self, light_num: LightNumber) -> Result<StateChangeResult> {
+        let light = self.get_light(light_num).await?;
+        self.set_state(light_num, &LightState::new().on(!light.state.is_on())).await
+    }
+
+    /// Snapshots the given lights, or every light if none are given
+    async fn capture_scene(&self, name: &str, light_nums: &[LightNumber]) -> Result<SavedScene> {
+        let lights: LightCollection = self
+            .get_all_lights()
+            .await?
+            .into_iter()
+            .filter(|(light_num, _)| light_nums.is_empty() || light_nums.contains(light_num))
+            .collect();
+
+        Ok(SavedScene::capture(name, &lights))
+    }
+
+    /// Sets every light in a saved scene. Lights the backend rejects are reported in the result
+    /// rather than stopping the rest of the scene.
+    async fn apply_scene(&self, scene: &SavedScene, transition_time: Option<u16>) -> Result<StateChangeResult> {
+        let mut states: Vec<_> = scene
+            .states
+            .iter()
+            .map(|(light_num, state)| {
+                let mut state = state.clone();
+                state.transitiontime = transition_time;
+                (*light_num, state)
+            })
+            .collect();
+        states.sort_unstable_by_key(|(light_num, _)| *light_num);
+
+        let mut result = StateChangeResult::default();
+        for (_, light_result) in self.set_states(&states).await {
+            match light_result {
+                Ok(light_result) => {
+                    result.applied.extend(light_result.applied);
+                    result.failed.extend(light_result.failed);
+                }
+                Err(HueError::Bridge(error)) => result.failed.push(error),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Polls every light on an interval and broadcasts what changed between polls
+    fn watch_lights(&self, interval: Duration) -> LightWatcher {
+        LightWatcher::spawn(self.clone(), interval)
+    }
+
+    /// Rate limits state changes to what a bridge can handle, merging changes to lights that are still waiting
+    fn write_queue(&self) -> WriteQueue {
+        WriteQueue::spawn(self.clone(), write_queue::BRIDGE_COMMANDS_PER_SECOND, write_queue::DEFAULT_MAX_PENDING)
+    }
+}
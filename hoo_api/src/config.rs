@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use hoo_api_types::{LightSelector, SelectorError};
+
+use crate::retry::RequestPolicy;
+
+/// Settings shared by the CLI and server, kept in `$XDG_CONFIG_HOME/hoo/config.toml` by default:
+///
+/// ```toml
+/// default_profile = "home"
+///
+/// [bridges.upstairs]
+/// base_uri = "https://192.168.1.20"
+/// user_id = "..."
+/// # Both optional. Over HTTPS the bridge's certificate has to be for this bridge id and have this SHA-256
+/// # fingerprint. Without a fingerprint, the first certificate seen is trusted and remembered.
+/// bridge_id = "001788fffe4b5a6c"
+/// fingerprint = "..."
+///
+/// [profiles.home]
+/// bridge = "upstairs"
+/// # Deciseconds, used for changes that don't give their own
+/// transition_time = 4
+/// # Milliseconds to wait for the bridge, and how many times to retry reads it doesn't answer
+/// bridge_timeout = 5000
+/// bridge_retries = 2
+/// bind = "0.0.0.0:8000"
+/// tls_cert = "/etc/hoo/cert.pem"
+/// tls_key = "/etc/hoo/key.pem"
+///
+/// # Browser origins allowed to call the server. Any origin if left out.
+/// cors_origins = ["https://hoo.example.com"]
+///
+/// [profiles.home.aliases]
+/// desk = "name:Desk*"
+/// downstairs = "group:Living room,group:Kitchen"
+///
+/// # Server API tokens. The server is open to anyone if there are none. `hoo token` makes new ones.
+/// [[profiles.home.tokens]]
+/// name = "phone"
+/// hash = "..."
+/// permission = "control"
+/// lights = "desk,3"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub bridges: HashMap<String, BridgeCredentials>,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BridgeCredentials {
+    pub base_uri: String,
+    pub user_id: String,
+    pub bridge_id: Option<String>,
+    pub fingerprint: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// Can be left out if there's only one bridge
+    pub bridge: Option<String>,
+    pub transition_time: Option<u16>,
+    pub bridge_timeout: Option<u64>,
+    pub bridge_retries: Option<u32>,
+    pub bind: Option<SocketAddr>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Listen on a Unix domain socket instead of `bind`
+    pub socket: Option<PathBuf>,
+    #[serde(default)]
+    pub cors_origins: Vec<String>,
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    #[serde(default)]
+    pub tokens: Vec<TokenConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    pub name: String,
+    /// From `hash_token`. The token itself is never stored.
+    pub hash: String,
+    #[serde(default)]
+    pub permission: Permission,
+    /// A light selector. Tokens with one can only control those lights, and nothing that affects others.
+    pub lights: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Only reads
+    #[default]
+    Read,
+    /// Reads and changes
+    Control,
+}
+
+/// A server API token with its light selector parsed and aliases expanded
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub name: String,
+    pub hash: String,
+    pub permission: Permission,
+    pub lights: Option<LightSelector>,
+}
+
+/// How tokens are stored in the config file: hex encoded SHA-256. Tokens are long and random, so there's no need for
+/// a slow, salted password hash.
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A profile with its bridge looked up and its aliases parsed. Everything is optional so settings given on the
+/// command line or in the environment can fill the gaps.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub name: Option<String>,
+    pub bridge: Option<BridgeCredentials>,
+    pub transition_time: Option<u16>,
+    pub bridge_timeout: Option<u64>,
+    pub bridge_retries: Option<u32>,
+    pub bind: Option<SocketAddr>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    pub cors_origins: Vec<String>,
+    pub aliases: HashMap<String, LightSelector>,
+    pub tokens: Vec<ApiToken>,
+}
+
+impl BridgeCredentials {
+    /// Credentials that don't pin the bridge's id or certificate, like the ones from the environment
+    pub fn new(base_uri: String, user_id: String) -> Self {
+        Self {
+            base_uri,
+            user_id,
+            bridge_id: None,
+            fingerprint: None,
+        }
+    }
+}
+
+impl Profile {
+    /// Which bridge to use, given the credentials from `HUE_BASE_URI` and `HUE_USER_ID` if both are set. A profile
+    /// that was asked for by name wins over the environment, otherwise the environment wins over the default profile.
+    pub fn credentials(
+        &self,
+        env: Option<(String, String)>,
+        explicit_profile: bool,
+    ) -> Result<BridgeCredentials, ConfigError> {
+        let from_env = env.map(|(base_uri, user_id)| BridgeCredentials::new(base_uri, user_id));
+        let from_profile = self.bridge.clone();
+        let credentials = if explicit_profile {
+            from_profile.or(from_env)
+        } else {
+            from_env.or(from_profile)
+        };
+        credentials.ok_or(ConfigError::NoBridge)
+    }
+
+    /// The default request policy with the timeout and retries given here, or as overridden by `timeout` and
+    /// `retries`
+    pub fn request_policy(&self, timeout: Option<u64>, retries: Option<u32>) -> RequestPolicy {
+        let default = RequestPolicy::default();
+        RequestPolicy {
+            timeout: timeout
+                .or(self.bridge_timeout)
+                .map(Duration::from_millis)
+                .unwrap_or(default.timeout),
+            retries: retries.or(self.bridge_retries).unwrap_or(default.retries),
+            ..default
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("hoo").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, or at the default path if none is given. It's fine for there to be nothing at
+    /// the default path, but a path that was asked for has to exist.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        toml::from_str(&contents).map_err(|source| ConfigError::Parse { path, source })
+    }
+
+    /// Picks the named profile, or the default one. Without either, a profile called "default" is used if there is
+    /// one, and an empty profile if not.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, ConfigError> {
+        let name = name.or(self.default_profile.as_deref());
+        let (name, profile) = match name {
+            Some(name) => {
+                let profile = self
+                    .profiles
+                    .get(name)
+                    .ok_or_else(|| ConfigError::UnknownProfile(name.to_string()))?;
+                (Some(name), profile.clone())
+            }
+            None => match self.profiles.get("default") {
+                Some(profile) => (Some("default"), profile.clone()),
+                None => (None, ProfileConfig::default()),
+            },
+        };
+
+        let bridge = match &profile.bridge {
+            Some(bridge) => Some(self.bridges.get(bridge).cloned().ok_or_else(|| ConfigError::UnknownBridge {
+                profile: name.unwrap_or("default").to_string(),
+                bridge: bridge.clone(),
+            })?),
+            None if self.bridges.len() == 1 => self.bridges.values().next().cloned(),
+            None => None,
+        };
+
+        let aliases = profile
+            .aliases
+            .iter()
+            .map(|(alias, selector)| match selector.parse() {
+                Ok(selector) => Ok((alias.clone(), selector)),
+                Err(source) => Err(ConfigError::InvalidAlias {
+                    alias: alias.clone(),
+                    source,
+                }),
+            })
+            .collect::<Result<HashMap<String, LightSelector>, _>>()?;
+
+        let tokens = profile
+            .tokens
+            .iter()
+            .map(|token| {
+                let lights = token
+                    .lights
+                    .as_ref()
+                    .map(|lights| lights.parse().and_then(|selector: LightSelector| selector.expand_aliases(&aliases)))
+                    .transpose()
+                    .map_err(|source| ConfigError::InvalidTokenLights {
+                        token: token.name.clone(),
+                        source,
+                    })?;
+                Ok(ApiToken {
+                    name: token.name.clone(),
+                    hash: token.hash.to_lowercase(),
+                    permission: token.permission,
+                    lights,
+                })
+            })
+            .collect::<Result<_, ConfigError>>()?;
+
+        Ok(Profile {
+            name: name.map(str::to_string),
+            bridge,
+            transition_time: profile.transition_time,
+            bridge_timeout: profile.bridge_timeout,
+            bridge_retries: profile.bridge_retries,
+            bind: profile.bind,
+            tls_cert: profile.tls_cert,
+            tls_key: profile.tls_key,
+            socket: profile.socket,
+            cors_origins: profile.cors_origins,
+            aliases,
+            tokens,
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Couldn't read config file {}: {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("Invalid config file {}: {source}", path.display())]
+    Parse { path: PathBuf, source: toml::de::Error },
+    #[error("No profile named {0:?} in the config file")]
+    UnknownProfile(String),
+    #[error("Profile {profile:?} uses bridge {bridge:?}, which isn't in the config file")]
+    UnknownBridge { profile: String, bridge: String },
+    #[error("Light alias {alias:?}: {source}")]
+    InvalidAlias { alias: String, source: SelectorError },
+    #[error("Lights for token {token:?}: {source}")]
+    InvalidTokenLights { token: String, source: SelectorError },
+    #[error("HUE_BASE_URI and HUE_USER_ID must be set, or a bridge added to the config file")]
+    NoBridge,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    const ONE_BRIDGE: &str = r#"
+        [bridges.upstairs]
+        base_uri = "http://192.168.1.20"
+        user_id = "upstairs-user"
+    "#;
+
+    #[test]
+    fn uses_the_only_bridge_by_default() {
+        let profile = config(ONE_BRIDGE).profile(None).unwrap();
+        assert_eq!(profile.name, None);
+        assert_eq!(profile.bridge.unwrap().user_id, "upstairs-user");
+
+        let two_bridges = format!("{}\n[bridges.downstairs]\nbase_uri = \"x\"\nuser_id = \"y\"\n", ONE_BRIDGE);
+        assert!(config(&two_bridges).profile(None).unwrap().bridge.is_none());
+    }
+
+    #[test]
+    fn picks_the_default_profile() {
+        let config = config(&format!(
+            "default_profile = \"home\"\n{}\n[profiles.home]\ntransition_time = 4\n[profiles.default]\n",
+            ONE_BRIDGE
+        ));
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.name.as_deref(), Some("home"));
+        assert_eq!(profile.transition_time, Some(4));
+        assert_eq!(config.profile(Some("default")).unwrap().transition_time, None);
+    }
+
+    #[test]
+    fn rejects_unknown_profiles_and_bridges() {
+        let error = config(ONE_BRIDGE).profile(Some("away")).unwrap_err();
+        assert!(matches!(error, ConfigError::UnknownProfile(name) if name == "away"));
+
+        let error = config("[profiles.home]\nbridge = \"upstairs\"\n").profile(Some("home")).unwrap_err();
+        assert!(
+            matches!(&error, ConfigError::UnknownBridge { profile, bridge } if profile == "home" && bridge == "upstairs"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn expands_aliases_in_token_lights() {
+        let config = config(
+            r#"
+            [profiles.default.aliases]
+            desk = "name:Desk*"
+
+            [[profiles.default.tokens]]
+            name = "phone"
+            hash = "ABCDEF"
+            lights = "desk,3"
+            "#,
+        );
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.aliases["desk"], "name:Desk*".parse().unwrap());
+
+        let token = &profile.tokens[0];
+        assert_eq!(token.hash, "abcdef");
+        assert_eq!(token.permission, Permission::Read);
+        assert_eq!(token.lights, Some("name:Desk*,3".parse().unwrap()));
+
+        let error = self::config("[profiles.default.aliases]\ndesk = \"nope!\"\n").profile(None).unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidAlias { alias, .. } if alias == "desk"));
+    }
+
+    #[test]
+    fn credentials_come_from_the_profile_only_when_asked_for() {
+        let profile = config(ONE_BRIDGE).profile(None).unwrap();
+        let env = || Some(("http://10.0.0.2".to_string(), "env-user".to_string()));
+
+        assert_eq!(profile.credentials(env(), false).unwrap().user_id, "env-user");
+        assert_eq!(profile.credentials(env(), true).unwrap().user_id, "upstairs-user");
+        assert_eq!(profile.credentials(None, false).unwrap().user_id, "upstairs-user");
+        assert!(matches!(Profile::default().credentials(None, true), Err(ConfigError::NoBridge)));
+    }
+}
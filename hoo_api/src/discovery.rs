@@ -0,0 +1,290 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use hyper::Uri;
+use tokio::net::UdpSocket;
+
+use hoo_api_types::BridgeConfig;
+
+use crate::deserialize_response;
+use crate::error::Result;
+
+const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+    HOST: 239.255.255.250:1900\r\n\
+    MAN: \"ssdp:discover\"\r\n\
+    MX: 2\r\n\
+    ST: ssdp:all\r\n\r\n";
+
+const MDNS_ADDRESS: &str = "224.0.0.251:5353";
+const MDNS_SERVICE: &str = "_hue._tcp.local";
+const DNS_TYPE_PTR: u16 = 12;
+const DNS_CLASS_IN: u16 = 1;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Host names bridges usually register on home networks, tried when SSDP and mDNS find nothing
+pub const DEFAULT_FALLBACK_URIS: &[&str] = &["http://philips-hue", "http://philips-hue.local"];
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredBridge {
+    pub base_uri: String,
+    pub config: BridgeConfig,
+}
+
+/// Finds bridges on the LAN with SSDP and mDNS at the same time, then probes each fallback URI they didn't already
+/// find. Bridges are deduplicated by bridge id. If a search can't run at all, the other one and the fallbacks are
+/// still tried, and its error is only returned when none of them finds a bridge either.
+pub async fn discover_bridges(timeout: Duration, fallback_uris: &[String]) -> Result<Vec<DiscoveredBridge>> {
+    let (ssdp, mdns) = futures::join!(ssdp_search(timeout), mdns_search(timeout));
+
+    let mut found = Vec::new();
+    let mut search_error = None;
+    for search in [ssdp, mdns] {
+        match search {
+            Ok(base_uris) => found.extend(base_uris),
+            Err(e) => search_error = search_error.or(Some(e)),
+        }
+    }
+
+    let mut candidates: Vec<String> = Vec::new();
+    for uri in found.into_iter().chain(fallback_uris.iter().cloned()) {
+        if !candidates.contains(&uri) {
+            candidates.push(uri);
+        }
+    }
+
+    let mut bridges: Vec<DiscoveredBridge> = Vec::new();
+    for base_uri in candidates {
+        let config = match probe_bridge(&base_uri).await {
+            Ok(config) => config,
+            Err(_) => continue,
+        };
+
+        if bridges.iter().all(|b| b.config.bridgeid != config.bridgeid) {
+            bridges.push(DiscoveredBridge { base_uri, config });
+        }
+    }
+
+    match search_error {
+        Some(e) if bridges.is_empty() => Err(e),
+        _ => Ok(bridges),
+    }
+}
+
+/// Sends an SSDP M-SEARCH and returns the base URI of every Hue bridge that answers before the timeout
+pub async fn ssdp_search(timeout: Duration) -> Result<Vec<String>> {
+    let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.send_to(SSDP_SEARCH.as_bytes(), SSDP_ADDRESS).await?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 2048];
+    let mut base_uris = Vec::new();
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let len = match tokio::time::timeout(remaining, socket.recv_from(&mut buf)).await {
+            Ok(result) => result?.0,
+            Err(_) => break,
+        };
+
+        let response = String::from_utf8_lossy(&buf[..len]);
+        if let Some(base_uri) = parse_ssdp_response(&response) {
+            if !base_uris.contains(&base_uri) {
+                base_uris.push(base_uri);
+            }
+        }
+    }
+
+    Ok(base_uris)
+}
+
+/// Asks for `_hue._tcp` services over mDNS and returns a base URI for every bridge that answers before the timeout.
+/// The query comes from an ephemeral port, so bridges answer it directly rather than to the whole network.
+pub async fn mdns_search(timeout: Duration) -> Result<Vec<String>> {
+    let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.send_to(&mdns_query(), MDNS_ADDRESS).await?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 4096];
+    let mut base_uris = Vec::new();
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let (len, from) = match tokio::time::timeout(remaining, socket.recv_from(&mut buf)).await {
+            Ok(result) => result?,
+            Err(_) => break,
+        };
+
+        // Bridges answer from their own address, which is all that's needed to probe them
+        let base_uri = format!("http://{}", from.ip());
+        if answers_hue_service(&buf[..len]) && !base_uris.contains(&base_uri) {
+            base_uris.push(base_uri);
+        }
+    }
+
+    Ok(base_uris)
+}
+
+/// Fetches the unauthenticated bridge config, which also confirms the URI points at a Hue bridge
+pub async fn probe_bridge(base_uri: &str) -> Result<BridgeConfig> {
+    let uri = Uri::from_str(&format!("{}/api/config", base_uri))?;
+    let client = hyper::Client::new();
+    let response = match tokio::time::timeout(PROBE_TIMEOUT, client.get(uri)).await {
+        Ok(response) => response?,
+        Err(_) => return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into()),
+    };
+
+    deserialize_response(response).await
+}
+
+fn parse_ssdp_response(response: &str) -> Option<String> {
+    let mut location = None;
+    let mut is_hue = false;
+
+    for line in response.lines() {
+        let (name, value) = match line.find(':') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => continue,
+        };
+
+        if name.eq_ignore_ascii_case("location") {
+            location = Some(value);
+        } else if name.eq_ignore_ascii_case("hue-bridgeid")
+            || (name.eq_ignore_ascii_case("server") && value.contains("IpBridge"))
+        {
+            is_hue = true;
+        }
+    }
+
+    if !is_hue {
+        return None;
+    }
+
+    let uri = Uri::from_str(location?).ok()?;
+    let scheme = uri.scheme_str().unwrap_or("http");
+    let authority = uri.authority()?;
+    Some(format!("{}://{}", scheme, authority))
+}
+
+/// A DNS query for the PTR records of the Hue service
+fn mdns_query() -> Vec<u8> {
+    // Id 0, standard query, one question
+    let mut query = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in MDNS_SERVICE.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&DNS_TYPE_PTR.to_be_bytes());
+    query.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    query
+}
+
+/// Whether a DNS packet is a response with a PTR record for the Hue service in any of its sections
+fn answers_hue_service(packet: &[u8]) -> bool {
+    let header = |index: usize| u16::from_be_bytes([packet[index], packet[index + 1]]);
+    if packet.len() < 12 || header(2) & 0x8000 == 0 {
+        return false;
+    }
+
+    let mut position = 12;
+    for _ in 0..header(4) {
+        position = match dns_name(packet, position) {
+            Some((_, end)) => end + 4,
+            None => return false,
+        };
+    }
+
+    let records = u32::from(header(6)) + u32::from(header(8)) + u32::from(header(10));
+    for _ in 0..records {
+        let (name, end) = match dns_name(packet, position) {
+            Some(name) => name,
+            None => return false,
+        };
+        let fields = match packet.get(end..end + 10) {
+            Some(fields) => fields,
+            None => return false,
+        };
+        let record_type = u16::from_be_bytes([fields[0], fields[1]]);
+        if record_type == DNS_TYPE_PTR && name.eq_ignore_ascii_case(MDNS_SERVICE) {
+            return true;
+        }
+        position = end + 10 + usize::from(u16::from_be_bytes([fields[8], fields[9]]));
+    }
+
+    false
+}
+
+/// Reads the possibly compressed name at `position`, returning it and where the record carries on after it
+fn dns_name(packet: &[u8], mut position: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Every pointer has to land before the last one did, so following them always ends
+    let mut earliest = position;
+
+    loop {
+        let len = *packet.get(position)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(position + 1)));
+        } else if len & 0xC0 == 0xC0 {
+            let target = (len & 0x3F) << 8 | *packet.get(position + 1)? as usize;
+            if target >= earliest {
+                return None;
+            }
+            end = end.or(Some(position + 2));
+            earliest = target;
+            position = target;
+        } else {
+            let label = packet.get(position + 1..position + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            position += 1 + len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response repeating the query's question, with answers that point back at its name
+    fn response(answer_type: u16) -> Vec<u8> {
+        let mut packet = mdns_query();
+        packet[2] = 0x84;
+        packet[7] = 2;
+        // An A record first, so finding the PTR means skipping over it
+        packet.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 120, 0, 4, 192, 168, 1, 2]);
+        packet.extend_from_slice(&[0xC0, 12]);
+        packet.extend_from_slice(&answer_type.to_be_bytes());
+        packet.extend_from_slice(&[0, 1, 0, 0, 0, 120, 0, 9, 6]);
+        packet.extend_from_slice(b"bridge");
+        packet.extend_from_slice(&[0xC0, 12]);
+        packet
+    }
+
+    #[test]
+    fn finds_hue_answers() {
+        assert!(answers_hue_service(&response(DNS_TYPE_PTR)));
+        assert!(!answers_hue_service(&response(16)));
+        // The query itself isn't an answer
+        assert!(!answers_hue_service(&mdns_query()));
+    }
+
+    #[test]
+    fn reads_compressed_names() {
+        let packet = response(DNS_TYPE_PTR);
+        let ptr_data = packet.len() - 9;
+        assert_eq!(dns_name(&packet, 12), Some((MDNS_SERVICE.to_string(), 29)));
+        assert_eq!(dns_name(&packet, ptr_data), Some((format!("bridge.{}", MDNS_SERVICE), packet.len())));
+    }
+
+    #[test]
+    fn rejects_broken_packets() {
+        let packet = response(DNS_TYPE_PTR);
+        assert!(!answers_hue_service(&packet[..packet.len() - 12]));
+        // A pointer to itself, and a label leading back to the pointer before it
+        assert_eq!(dns_name(&[0xC0, 0], 0), None);
+        assert_eq!(dns_name(&[1, b'a', 0xC0, 0], 2), None);
+        assert_eq!(dns_name(&[3, b'h', b'u'], 0), None);
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use hoo_api_types::RuleError;
+
+use crate::tls::CertificateError;
+
+pub type Result<T> = std::result::Result<T, HueError>;
+
+#[derive(Debug, Error)]
+pub enum HueError {
+    #[error("Invalid URI: {0}")]
+    InvalidUri(#[from] hyper::http::uri::InvalidUri),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(#[from] hyper::http::Error),
+    #[error("Network error: {0}")]
+    Network(#[from] hyper::Error),
+    #[error("Untrusted bridge certificate: {0}")]
+    Certificate(#[from] CertificateError),
+    #[error("The bridge didn't answer within {0:?}")]
+    Timeout(Duration),
+    /// A 5xx response, which bridges give while they're busy or restarting
+    #[error("The bridge failed to answer: {0}")]
+    ServerError(hyper::StatusCode),
+    #[error("The bridge is unavailable after {failures} failed attempts in a row. Trying it again in {retry_in:?}")]
+    CircuitOpen { failures: u32, retry_in: Duration },
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to decode response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Response was not valid UTF-8: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+    #[error("Bridge error: {0}")]
+    Bridge(BridgeError),
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+    #[error("Invalid rule: {0}")]
+    InvalidRule(#[from] RuleError),
+}
+
+impl HueError {
+    pub fn bridge_error(&self) -> Option<&BridgeError> {
+        match self {
+            HueError::Bridge(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub fn bridge_error_kind(&self) -> Option<BridgeErrorKind> {
+        self.bridge_error().map(|error| error.kind)
+    }
+
+    /// Whether the bridge couldn't be reached, didn't answer or failed to, so trying again later might work
+    pub fn is_transient(&self) -> bool {
+        matches!(self, HueError::Network(_) | HueError::Timeout(_) | HueError::ServerError(_))
+    }
+}
+
+impl From<BridgeError> for HueError {
+    fn from(error: BridgeError) -> Self {
+        HueError::Bridge(error)
+    }
+}
+
+/// An entry of the error array the bridge returns, e.g.
+/// `{"type":1,"address":"/lights","description":"unauthorized user"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BridgeError {
+    #[serde(rename = "type")]
+    pub kind: BridgeErrorKind,
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Display for BridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} (type {}", self.description, self.kind.code())?;
+        if !self.address.is_empty() {
+            write!(f, " at {}", self.address)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct BridgeErrorItem {
+    pub error: BridgeError,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u16", into = "u16")]
+pub enum BridgeErrorKind {
+    UnauthorizedUser,
+    InvalidJson,
+    ResourceNotAvailable,
+    MethodNotAvailable,
+    MissingParameters,
+    ParameterNotAvailable,
+    InvalidParameterValue,
+    ParameterNotModifiable,
+    TooManyItems,
+    PortalConnectionRequired,
+    LinkButtonNotPressed,
+    DeviceIsOff,
+    GroupTableFull,
+    DeviceUnreachable,
+    SceneBufferFull,
+    SensorListFull,
+    RuleEngineFull,
+    ScheduleListFull,
+    InternalError,
+    Other(u16),
+}
+
+impl BridgeErrorKind {
+    pub fn code(self) -> u16 {
+        match self {
+            BridgeErrorKind::UnauthorizedUser => 1,
+            BridgeErrorKind::InvalidJson => 2,
+            BridgeErrorKind::ResourceNotAvailable => 3,
+            BridgeErrorKind::MethodNotAvailable => 4,
+            BridgeErrorKind::MissingParameters => 5,
+            BridgeErrorKind::ParameterNotAvailable => 6,
+            BridgeErrorKind::InvalidParameterValue => 7,
+            BridgeErrorKind::ParameterNotModifiable => 8,
+            BridgeErrorKind::TooManyItems => 11,
+            BridgeErrorKind::PortalConnectionRequired => 12,
+            BridgeErrorKind::LinkButtonNotPressed => 101,
+            BridgeErrorKind::DeviceIsOff => 201,
+            BridgeErrorKind::GroupTableFull => 301,
+            BridgeErrorKind::DeviceUnreachable => 304,
+            BridgeErrorKind::SceneBufferFull => 402,
+            BridgeErrorKind::SensorListFull => 502,
+            BridgeErrorKind::RuleEngineFull => 601,
+            BridgeErrorKind::ScheduleListFull => 701,
+            BridgeErrorKind::InternalError => 901,
+            BridgeErrorKind::Other(code) => code,
+        }
+    }
+}
+
+impl From<u16> for BridgeErrorKind {
+    fn from(code: u16) -> Self {
+        match code {
+            1 => BridgeErrorKind::UnauthorizedUser,
+            2 => BridgeErrorKind::InvalidJson,
+            3 => BridgeErrorKind::ResourceNotAvailable,
+            4 => BridgeErrorKind::MethodNotAvailable,
+            5 => BridgeErrorKind::MissingParameters,
+            6 => BridgeErrorKind::ParameterNotAvailable,
+            7 => BridgeErrorKind::InvalidParameterValue,
+            8 => BridgeErrorKind::ParameterNotModifiable,
+            11 => BridgeErrorKind::TooManyItems,
+            12 => BridgeErrorKind::PortalConnectionRequired,
+            101 => BridgeErrorKind::LinkButtonNotPressed,
+            201 => BridgeErrorKind::DeviceIsOff,
+            301 => BridgeErrorKind::GroupTableFull,
+            304 => BridgeErrorKind::DeviceUnreachable,
+            402 => BridgeErrorKind::SceneBufferFull,
+            502 => BridgeErrorKind::SensorListFull,
+            601 => BridgeErrorKind::RuleEngineFull,
+            701 => BridgeErrorKind::ScheduleListFull,
+            901 => BridgeErrorKind::InternalError,
+            code => BridgeErrorKind::Other(code),
+        }
+    }
+}
+
+impl From<BridgeErrorKind> for u16 {
+    fn from(kind: BridgeErrorKind) -> Self {
+        kind.code()
+    }
+}
@@ -1,32 +1,106 @@
-pub use hoo_api_types::{Color, Light, LightCollection, LightNumber, LightState};
+pub mod backend;
+pub mod config;
+pub mod discovery;
+pub mod error;
+pub mod registration;
+pub mod response;
+pub mod retry;
+pub mod scene_store;
+pub mod tls;
+pub mod watcher;
+pub mod write_queue;
+
+pub use hoo_api_types::{
+    BridgeConfig, Color, Group, GroupAction, GroupAttributes, GroupCollection, GroupNumber, GroupType, Light,
+    LightCollection, LightColorMode, LightNumber, LightState, LightStateError, Rule, RuleCollection, RuleId,
+    LightSelector, SavedScene, Scene, SceneAttributes, SceneCollection, SceneId, SceneType, Schedule,
+    ScheduleCollection, ScheduleCommand, ScheduleId, ScheduleStatus, SelectorError, Sensor, SensorCollection,
+    SensorConfig, SensorNumber, SensorState, SensorType, MAX_SAT,
+};
+pub use backend::LightBackend;
+pub use config::{hash_token, ApiToken, BridgeCredentials, Config, ConfigError, Permission, Profile};
+pub use discovery::{discover_bridges, DiscoveredBridge};
+pub use error::{BridgeError, BridgeErrorKind, HueError};
+pub use registration::{register_user, wait_for_registration};
+pub use response::{AppliedChange, StateChangeResult};
+pub use retry::RequestPolicy;
+pub use scene_store::SceneStore;
+pub use tls::{BridgeTrust, CertificateError, KnownBridges};
+pub use watcher::{diff_lights, LightEvent, LightWatcher};
+pub use write_queue::{WriteOutcome, WriteQueue, WriteQueueStats};
 
 use std::collections::HashMap;
 use std::str::FromStr;
 
-use anyhow::Result;
-use hyper::client::HttpConnector;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
 use hyper::{body, Body, Request, Response, Uri};
 
 use hoo_api_types::LightEffect;
 
+use crate::error::{BridgeErrorItem, Result};
+use crate::response::BridgeResponseItem;
+use crate::retry::CircuitBreaker;
+use crate::tls::BridgeConnector;
+
+/// How many requests `set_states` keeps in flight at once
+pub const MAX_CONCURRENT_REQUESTS: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct HueClient {
-    pub client: hyper::Client<HttpConnector>,
+    client: hyper::Client<BridgeConnector>,
     base_uri: String,
+    user_id: String,
+    policy: RequestPolicy,
+    breaker: CircuitBreaker,
 }
 
 impl HueClient {
+    /// Over HTTPS, trusts whatever certificate the bridge presents first, for as long as the client is around
     pub fn new(base_uri: &str, user_id: &str) -> Self {
+        Self::with_trust(base_uri, user_id, BridgeTrust::default())
+    }
+
+    pub fn with_trust(base_uri: &str, user_id: &str, trust: BridgeTrust) -> Self {
         let base_uri = format!("{}/api/{}", base_uri, user_id);
         Self {
-            client: hyper::Client::new(),
+            client: tls::client(trust),
             base_uri: base_uri.to_string(),
+            user_id: user_id.to_string(),
+            policy: RequestPolicy::default(),
+            breaker: CircuitBreaker::default(),
         }
     }
 
+    pub fn with_policy(mut self, policy: RequestPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &RequestPolicy {
+        &self.policy
+    }
+
+    /// Whether requests are failing straight away because the bridge has been failing
+    pub fn is_circuit_open(&self) -> bool {
+        self.breaker.is_open()
+    }
+
+    /// Retried with backoff if the bridge can't be reached or doesn't answer in time
     pub async fn get(&self, endpoint: &str) -> Result<Response<Body>> {
         let uri = Uri::from_str(&format!("{}/{}", self.base_uri, endpoint))?;
-        Ok(self.client.get(uri).await?)
+
+        let mut retry = 0;
+        loop {
+            let request = Request::builder().method("GET").uri(uri.clone()).body(Body::empty())?;
+            match self.handle(request).await {
+                Err(e) if e.is_transient() && retry < self.policy.retries => {
+                    tokio::time::delay_for(self.policy.backoff_delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
     }
 
     pub async fn put<T>(&self, endpoint: &str, body: T) -> Result<Response<Body>> 
@@ -36,76 +110,78 @@ impl HueClient {
         let request = Request::builder()
             .method("PUT")
             .uri(uri)
-            .body(body.into())
-            .unwrap();
+            .body(body.into())?;
 
         self.handle(request).await
     }
 
-    pub async fn handle(&self, request: Request<Body>) -> Result<Response<Body>> {
-        Ok(self.client.request(request).await?)
-    }
-
-    pub async fn get_all_lights_response(&self) -> Result<Response<Body>> {
-        self.get("lights").await
-    }
+    pub async fn post<T>(&self, endpoint: &str, body: T) -> Result<Response<Body>>
+    where T: Into<Body>
+    {
+        let uri = Uri::from_str(&format!("{}/{}", self.base_uri, endpoint))?;
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .body(body.into())?;
 
-    pub async fn get_all_lights(&self) -> Result<LightCollection> {
-        let response = self.get_all_lights_response().await?;
-        let lights: HashMap<u8, Light> = deserialize_response(response).await?;
-        Ok(lights)
+        self.handle(request).await
     }
 
-    pub async fn get_active_lights(&self) -> Result<LightCollection> {
-        let active_lights = self
-            .get_all_lights()
-            .await?
-            .into_iter()
-            .filter(|(_, l)| l.state.is_on() && l.state.is_reachable())
-            .collect();
+    pub async fn delete(&self, endpoint: &str) -> Result<Response<Body>> {
+        let uri = Uri::from_str(&format!("{}/{}", self.base_uri, endpoint))?;
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(uri)
+            .body(Body::empty())?;
 
-        Ok(active_lights)
+        self.handle(request).await
     }
 
-    pub async fn get_light_response(&self, light_number: u8) -> Result<Response<Body>> {
-        let uri = format!("lights/{}", light_number);
-        self.get(&uri).await
-    }
+    /// Sends a request once, failing straight away if the circuit breaker is open. The timeout covers reading the
+    /// whole response, which is returned with its body already in memory.
+    pub async fn handle(&self, request: Request<Body>) -> Result<Response<Body>> {
+        self.breaker.check()?;
 
-    pub async fn get_light(&self, light_number: u8) -> Result<Light> {
-        let response = self.get_light_response(light_number).await?;
-        deserialize_response(response).await
-    }
+        let result = match tokio::time::timeout(self.policy.timeout, self.send(request)).await {
+            Ok(result) => result,
+            Err(_) => Err(HueError::Timeout(self.policy.timeout)),
+        };
+        match &result {
+            Ok(_) => self.breaker.record_success(),
+            Err(e) if e.is_transient() => self.breaker.record_failure(&self.policy),
+            Err(_) => {}
+        }
 
-    pub async fn set_state(&self, light_number: u8, state: &LightState) -> Result<Response<Body>> {
-        let body = serde_json::to_string(state)?;
-        self.set_state_from_body(light_number, body.into()).await
+        result
     }
 
-    pub async fn set_state_from_body(&self, light_number: u8, body: Body) -> Result<Response<Body>> {
-        let uri = format!("lights/{}/state", light_number);
-        self.put(&uri, body).await
+    async fn send(&self, request: Request<Body>) -> Result<Response<Body>> {
+        let response = self.client.request(request).await.map_err(tls::request_error)?;
+        let (parts, body) = response.into_parts();
+        let body = body::to_bytes(body).await?;
+        if parts.status.is_server_error() {
+            return Err(HueError::ServerError(parts.status));
+        }
+
+        Ok(Response::from_parts(parts, Body::from(body)))
     }
 
-    pub async fn on(&self, light_number: u8) -> Result<Response<Body>> {
-        let state = LightState::new().on(true);
-        self.set_state(light_number, &state).await
+    pub async fn get_all_lights_response(&self) -> Result<Response<Body>> {
+        self.get("lights").await
     }
 
-    pub async fn off(&self, light_number: u8) -> Result<Response<Body>> {
-        let state = LightState::new().on(false);
-        self.set_state(light_number, &state).await
+    pub async fn get_light_response(&self, light_number: u8) -> Result<Response<Body>> {
+        let uri = format!("lights/{}", light_number);
+        self.get(&uri).await
     }
 
-    pub async fn toggle(&self, light_number: u8) -> Result<Response<Body>> {
-        let light = self.get_light(light_number).await?;
-        match light.state.on {
-            Some(is_on) if is_on => self.off(light_number).await,
-            _ => self.on(light_number).await,
-        }
+    pub async fn set_state_from_body(&self, light_number: u8, body: Body) -> Result<StateChangeResult> {
+        let uri = format!("lights/{}/state", light_number);
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
     }
 
-    pub async fn colorloop(&self, light_number: u8, enabled: bool) -> Result<Response<Body>> {
+    pub async fn colorloop(&self, light_number: u8, enabled: bool) -> Result<StateChangeResult> {
         let effect = if enabled {
             LightEffect::ColorLoop
         } else {
@@ -119,10 +195,226 @@ impl HueClient {
         &self,
         light_number: u8,
         transition_time: u16,
-    ) -> Result<Response<Body>> {
+    ) -> Result<StateChangeResult> {
         let state = LightState::new().transitiontime(transition_time);
         self.set_state(light_number, &state).await
     }
+
+    pub async fn get_all_scenes(&self) -> Result<SceneCollection> {
+        let response = self.get("scenes").await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn get_scene(&self, scene_id: &str) -> Result<Scene> {
+        let uri = format!("scenes/{}", scene_id);
+        let response = self.get(&uri).await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn create_scene(&self, attributes: &SceneAttributes) -> Result<SceneId> {
+        let body = serde_json::to_string(attributes)?;
+        let response = self.post("scenes", body).await?;
+        created_id(deserialize_state_change(response).await?)
+    }
+
+    pub async fn delete_scene(&self, scene_id: &str) -> Result<StateChangeResult> {
+        let uri = format!("scenes/{}", scene_id);
+        let response = self.delete(&uri).await?;
+        deserialize_state_change(response).await
+    }
+
+    /// Recalls a bridge scene. Group 0 contains every light, which is what light scenes need.
+    pub async fn recall_scene(&self, scene_id: &str, group_number: Option<GroupNumber>) -> Result<StateChangeResult> {
+        let action = GroupAction::new().scene(scene_id);
+        self.set_group_action(group_number.unwrap_or(0), &action).await
+    }
+
+    pub async fn get_all_schedules(&self) -> Result<ScheduleCollection> {
+        let response = self.get("schedules").await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn get_schedule(&self, schedule_id: &str) -> Result<Schedule> {
+        let uri = format!("schedules/{}", schedule_id);
+        let response = self.get(&uri).await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn create_schedule(&self, schedule: &Schedule) -> Result<ScheduleId> {
+        let body = serde_json::to_string(schedule)?;
+        let response = self.post("schedules", body).await?;
+        created_id(deserialize_state_change(response).await?)
+    }
+
+    pub async fn update_schedule(&self, schedule_id: &str, schedule: &Schedule) -> Result<StateChangeResult> {
+        let uri = format!("schedules/{}", schedule_id);
+        let body = serde_json::to_string(schedule)?;
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
+    }
+
+    pub async fn delete_schedule(&self, schedule_id: &str) -> Result<StateChangeResult> {
+        let uri = format!("schedules/{}", schedule_id);
+        let response = self.delete(&uri).await?;
+        deserialize_state_change(response).await
+    }
+
+    pub async fn get_sensors(&self) -> Result<SensorCollection> {
+        let response = self.get("sensors").await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn get_sensor(&self, sensor_number: SensorNumber) -> Result<Sensor> {
+        let uri = format!("sensors/{}", sensor_number);
+        let response = self.get(&uri).await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn set_sensor_config(&self, sensor_number: SensorNumber, config: &SensorConfig) -> Result<StateChangeResult> {
+        let uri = format!("sensors/{}/config", sensor_number);
+        let body = serde_json::to_string(config)?;
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
+    }
+
+    /// Only CLIP sensors accept state changes
+    pub async fn set_sensor_state(&self, sensor_number: SensorNumber, state: &SensorState) -> Result<StateChangeResult> {
+        let uri = format!("sensors/{}/state", sensor_number);
+        let body = serde_json::to_string(state)?;
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
+    }
+
+    pub async fn get_all_rules(&self) -> Result<RuleCollection> {
+        let response = self.get("rules").await?;
+        deserialize_response(response).await
+    }
+
+    pub async fn get_rule(&self, rule_id: &str) -> Result<Rule> {
+        let uri = format!("rules/{}", rule_id);
+        let response = self.get(&uri).await?;
+        deserialize_response(response).await
+    }
+
+    /// Validates the rule before sending it to the bridge
+    pub async fn create_rule(&self, rule: &Rule) -> Result<RuleId> {
+        rule.validate()?;
+        let body = serde_json::to_string(&rule.definition())?;
+        let response = self.post("rules", body).await?;
+        created_id(deserialize_state_change(response).await?)
+    }
+
+    /// Validates the rule before sending it to the bridge
+    pub async fn update_rule(&self, rule_id: &str, rule: &Rule) -> Result<StateChangeResult> {
+        rule.validate()?;
+        let uri = format!("rules/{}", rule_id);
+        let body = serde_json::to_string(&rule.definition())?;
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
+    }
+
+    pub async fn delete_rule(&self, rule_id: &str) -> Result<StateChangeResult> {
+        let uri = format!("rules/{}", rule_id);
+        let response = self.delete(&uri).await?;
+        deserialize_state_change(response).await
+    }
+
+    /// A schedule command that sets a light's state, addressed with this client's username
+    pub fn light_state_command(&self, light_number: u8, state: &LightState) -> Result<ScheduleCommand> {
+        Ok(ScheduleCommand {
+            address: format!("/api/{}/lights/{}/state", self.user_id, light_number),
+            method: String::from("PUT"),
+            body: serde_json::to_value(state)?,
+        })
+    }
+
+    /// A schedule command that runs a group action, addressed with this client's username
+    pub fn group_action_command(&self, group_number: GroupNumber, action: &GroupAction) -> Result<ScheduleCommand> {
+        Ok(ScheduleCommand {
+            address: format!("/api/{}/groups/{}/action", self.user_id, group_number),
+            method: String::from("PUT"),
+            body: serde_json::to_value(action)?,
+        })
+    }
+}
+
+#[async_trait]
+impl LightBackend for HueClient {
+    async fn get_all_lights(&self) -> Result<LightCollection> {
+        let response = self.get_all_lights_response().await?;
+        let lights: HashMap<u8, Light> = deserialize_response(response).await?;
+        Ok(lights)
+    }
+
+    async fn get_light(&self, light_number: u8) -> Result<Light> {
+        let response = self.get_light_response(light_number).await?;
+        deserialize_response(response).await
+    }
+
+    async fn set_state(&self, light_number: u8, state: &LightState) -> Result<StateChangeResult> {
+        let body = serde_json::to_string(state)?;
+        self.set_state_from_body(light_number, body.into()).await
+    }
+
+    async fn get_all_groups(&self) -> Result<GroupCollection> {
+        let response = self.get("groups").await?;
+        deserialize_response(response).await
+    }
+
+    async fn get_group(&self, group_number: GroupNumber) -> Result<Group> {
+        let uri = format!("groups/{}", group_number);
+        let response = self.get(&uri).await?;
+        deserialize_response(response).await
+    }
+
+    async fn create_group(&self, attributes: &GroupAttributes) -> Result<GroupNumber> {
+        let body = serde_json::to_string(attributes)?;
+        let response = self.post("groups", body).await?;
+        let id = created_id(deserialize_state_change(response).await?)?;
+        id.parse()
+            .map_err(|_| HueError::UnexpectedResponse(format!("group id {} is not a number", id)))
+    }
+
+    async fn update_group(
+        &self,
+        group_number: GroupNumber,
+        attributes: &GroupAttributes,
+    ) -> Result<StateChangeResult> {
+        let uri = format!("groups/{}", group_number);
+        let body = serde_json::to_string(attributes)?;
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
+    }
+
+    async fn delete_group(&self, group_number: GroupNumber) -> Result<StateChangeResult> {
+        let uri = format!("groups/{}", group_number);
+        let response = self.delete(&uri).await?;
+        deserialize_state_change(response).await
+    }
+
+    async fn set_group_action(
+        &self,
+        group_number: GroupNumber,
+        action: &GroupAction,
+    ) -> Result<StateChangeResult> {
+        let uri = format!("groups/{}/action", group_number);
+        let body = serde_json::to_string(action)?;
+        let response = self.put(&uri, body).await?;
+        deserialize_state_change(response).await
+    }
+
+    /// Sends a few requests at a time rather than one after another
+    async fn set_states(&self, states: &[(LightNumber, LightState)]) -> Vec<(LightNumber, Result<StateChangeResult>)> {
+        let requests: Vec<_> = states
+            .iter()
+            .map(|(light_num, state)| async move { (*light_num, self.set_state(*light_num, state).await) })
+            .collect();
+
+        stream::iter(requests)
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
+            .await
+    }
 }
 
 pub async fn response_to_string(response: Response<Body>) -> Result<String> {
@@ -134,6 +426,28 @@ pub async fn deserialize_response<T>(response: Response<Body>) -> Result<T>
 where T: serde::de::DeserializeOwned,
 {
     let body_bytes = body::to_bytes(response.into_body()).await?;
+    if let Ok(mut errors) = serde_json::from_slice::<Vec<BridgeErrorItem>>(&body_bytes) {
+        if !errors.is_empty() {
+            return Err(errors.remove(0).error.into());
+        }
+    }
+
     let result = serde_json::from_slice(&body_bytes)?;
     Ok(result)
 }
+
+pub async fn deserialize_state_change(response: Response<Body>) -> Result<StateChangeResult> {
+    let items: Vec<BridgeResponseItem> = deserialize_response(response).await?;
+    StateChangeResult::from_items(items)
+}
+
+/// Reads the id the bridge assigned to a newly created resource
+fn created_id(result: StateChangeResult) -> Result<String> {
+    result
+        .applied
+        .iter()
+        .find(|change| change.address == "id")
+        .and_then(|change| change.value.as_str())
+        .map(String::from)
+        .ok_or_else(|| HueError::UnexpectedResponse(String::from("no id in creation response")))
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use hyper::{Body, Request, Uri};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::deserialize_response;
+use crate::error::{BridgeErrorKind, HueError, Result};
+use crate::response::BridgeResponseItem;
+use crate::retry::RequestPolicy;
+use crate::tls::{self, BridgeTrust};
+
+#[derive(Debug, Serialize)]
+struct RegistrationRequest<'a> {
+    devicetype: &'a str,
+}
+
+/// Asks the bridge to whitelist a new user. Fails with `LinkButtonNotPressed` unless
+/// the link button was pressed within the last 30 seconds.
+pub async fn register_user(base_uri: &str, devicetype: &str, trust: &BridgeTrust) -> Result<String> {
+    let uri = Uri::from_str(&format!("{}/api", base_uri))?;
+    let body = serde_json::to_string(&RegistrationRequest { devicetype })?;
+    let request = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .body(Body::from(body))?;
+
+    let timeout = RequestPolicy::default().timeout;
+    let client = tls::client(trust.clone());
+    let answer = async {
+        let response = client.request(request).await.map_err(tls::request_error)?;
+        deserialize_response::<Vec<BridgeResponseItem>>(response).await
+    };
+    let items = tokio::time::timeout(timeout, answer)
+        .await
+        .map_err(|_| HueError::Timeout(timeout))??;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            BridgeResponseItem::Success(values) => values
+                .get("username")
+                .and_then(Value::as_str)
+                .map(String::from),
+            BridgeResponseItem::Error(_) => None,
+        })
+        .ok_or_else(|| HueError::UnexpectedResponse(String::from("no username in registration response")))
+}
+
+/// Retries `register_user` every `poll_interval` until the link button is pressed or `timeout` runs out
+pub async fn wait_for_registration(
+    base_uri: &str,
+    devicetype: &str,
+    trust: &BridgeTrust,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<String> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match register_user(base_uri, devicetype, trust).await {
+            Err(e) if e.bridge_error_kind() == Some(BridgeErrorKind::LinkButtonNotPressed)
+                && Instant::now() + poll_interval < deadline =>
+            {
+                tokio::time::delay_for(poll_interval).await;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{BridgeError, HueError, Result};
+
+/// A single entry of the array the bridge returns for PUT and POST requests
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BridgeResponseItem {
+    Success(Value),
+    Error(BridgeError),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedChange {
+    pub address: String,
+    pub value: Value,
+}
+
+impl AppliedChange {
+    /// The last segment of the address, e.g. `on` for `/lights/1/state/on`
+    pub fn attribute(&self) -> &str {
+        self.address.rsplit('/').next().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateChangeResult {
+    pub applied: Vec<AppliedChange>,
+    pub failed: Vec<BridgeError>,
+}
+
+impl StateChangeResult {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn applied_attributes(&self) -> Vec<&str> {
+        self.applied.iter().map(AppliedChange::attribute).collect()
+    }
+
+    /// Fails with the first bridge error if nothing at all was applied
+    pub(crate) fn from_items(items: Vec<BridgeResponseItem>) -> Result<Self> {
+        let mut result = StateChangeResult::default();
+
+        for item in items {
+            match item {
+                BridgeResponseItem::Success(Value::Object(changes)) => {
+                    let applied = changes
+                        .into_iter()
+                        .map(|(address, value)| AppliedChange { address, value });
+                    result.applied.extend(applied);
+                }
+                // Deletions report a bare string like "/groups/1 deleted"
+                BridgeResponseItem::Success(value) => {
+                    result.applied.push(AppliedChange { address: String::new(), value });
+                }
+                BridgeResponseItem::Error(error) => result.failed.push(error),
+            }
+        }
+
+        if result.applied.is_empty() && !result.failed.is_empty() {
+            return Err(HueError::Bridge(result.failed.remove(0)));
+        }
+
+        Ok(result)
+    }
+}
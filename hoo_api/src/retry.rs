@@ -0,0 +1,97 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::error::{HueError, Result};
+
+/// How long to wait for a bridge, how to retry, and when to stop trying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestPolicy {
+    /// How long each attempt waits for the bridge's whole answer, including connecting
+    pub timeout: Duration,
+    /// How many more times GETs are tried after timing out, failing to connect or getting a 5xx. Changes are never retried, since
+    /// some of them, like brightness increments, would be applied twice.
+    pub retries: u32,
+    /// Roughly how long before the first retry. It doubles for each one after, and is jittered so clients that
+    /// failed together don't retry together.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Failed attempts in a row after which requests fail straight away, without waiting on a bridge that's down.
+    /// Zero never stops trying.
+    pub failure_threshold: u32,
+    /// How long requests fail straight away for before the bridge is tried again
+    pub cooldown: Duration,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            retries: 2,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RequestPolicy {
+    /// How long to wait before retry number `retry`, counting from zero. Somewhere between half the backoff and
+    /// all of it.
+    pub fn backoff_delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .backoff
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let millis = backoff.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2, millis + 1))
+    }
+}
+
+/// Counts failed attempts in a row, shared by every clone of a client, and opens once there are too many. While
+/// it's open, requests fail with `CircuitOpen`. Once the cooldown is over requests go through again, and the
+/// first failure opens it straight back up.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+    state: Arc<Mutex<BreakerState>>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn check(&self) -> Result<()> {
+        let state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if open_until > Instant::now() => Err(HueError::CircuitOpen {
+                failures: state.failures,
+                retry_in: open_until - Instant::now(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    pub fn record_failure(&self, policy: &RequestPolicy) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if policy.failure_threshold > 0 && state.failures >= policy.failure_threshold {
+            state.open_until = Some(Instant::now() + policy.cooldown);
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.check().is_err()
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use hoo_api_types::SavedScene;
+
+use crate::error::Result;
+
+/// Saved scenes kept in a JSON file, keyed by scene name
+#[derive(Debug, Clone)]
+pub struct SceneStore {
+    path: PathBuf,
+}
+
+impl SceneStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns no scenes if the file doesn't exist yet
+    pub fn load(&self) -> Result<HashMap<String, SavedScene>> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let contents = std::fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<SavedScene>> {
+        Ok(self.load()?.remove(name))
+    }
+
+    /// Replaces any scene with the same name
+    pub fn save(&self, scene: SavedScene) -> Result<()> {
+        let mut scenes = self.load()?;
+        scenes.insert(scene.name.clone(), scene);
+        self.write(&scenes)
+    }
+
+    /// Returns whether a scene with that name existed
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let mut scenes = self.load()?;
+        let existed = scenes.remove(name).is_some();
+        if existed {
+            self.write(&scenes)?;
+        }
+        Ok(existed)
+    }
+
+    fn write(&self, scenes: &HashMap<String, SavedScene>) -> Result<()> {
+        let contents = serde_json::to_string_pretty(scenes)?;
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! HTTPS to bridges. Bridges serve a self-signed certificate whose common name is the bridge id, so there's no CA
+//! to check it against. Instead the certificate's SHA-256 fingerprint is pinned: either given up front, or trusted
+//! the first time a bridge is seen and checked against from then on, the way SSH checks host keys.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use hyper::client::connect::{Connected, Connection};
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+use hyper::Uri;
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::TlsConnector;
+
+use crate::config::BridgeCredentials;
+use crate::error::{HueError, Result};
+
+/// Bridges are usually addressed by IP, which rustls won't take as a server name. The name is never checked, since
+/// the bridge id in the certificate is, so any valid one will do.
+const SERVER_NAME: &str = "hue-bridge";
+
+/// How to decide whether to trust a bridge's certificate. Only used for `https` base URIs.
+#[derive(Debug, Clone, Default)]
+pub struct BridgeTrust {
+    /// The bridge id the certificate has to be for
+    pub bridge_id: Option<String>,
+    /// The SHA-256 fingerprint of the certificate, in hex. Colons and case don't matter. Without one, the first
+    /// certificate a bridge presents is trusted.
+    pub fingerprint: Option<String>,
+    /// Where certificates trusted on first use are remembered. Without it they're only remembered for as long as
+    /// the client is around.
+    pub known_bridges: Option<KnownBridges>,
+}
+
+impl BridgeTrust {
+    /// Pins what the config file gives for the bridge, and otherwise remembers it in the default known bridges file
+    pub fn for_bridge(bridge: &BridgeCredentials) -> Self {
+        Self {
+            bridge_id: bridge.bridge_id.clone(),
+            fingerprint: bridge.fingerprint.clone(),
+            known_bridges: KnownBridges::default_path().map(KnownBridges::new),
+        }
+    }
+}
+
+/// A bridge's certificate as it was first seen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnownBridge {
+    pub bridge_id: String,
+    pub fingerprint: String,
+}
+
+/// Certificates trusted on first use, kept in a JSON file keyed by the bridge's host and port
+#[derive(Debug, Clone)]
+pub struct KnownBridges {
+    path: PathBuf,
+}
+
+impl KnownBridges {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("hoo").join("known_bridges.json"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns no bridges if the file doesn't exist yet
+    pub fn load(&self) -> Result<HashMap<String, KnownBridge>> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let contents = std::fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn get(&self, host: &str) -> Result<Option<KnownBridge>> {
+        Ok(self.load()?.remove(host))
+    }
+
+    pub fn save(&self, host: &str, bridge: KnownBridge) -> Result<()> {
+        let mut bridges = self.load()?;
+        bridges.insert(host.to_string(), bridge);
+        self.write(&bridges)
+    }
+
+    /// Forgets a bridge so its next certificate is trusted, e.g. after it was reset. Returns whether it was known.
+    pub fn remove(&self, host: &str) -> Result<bool> {
+        let mut bridges = self.load()?;
+        let existed = bridges.remove(host).is_some();
+        if existed {
+            self.write(&bridges)?;
+        }
+        Ok(existed)
+    }
+
+    fn write(&self, bridges: &HashMap<String, KnownBridge>) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(bridges)?;
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
+
+/// Why a bridge's certificate wasn't trusted
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum CertificateError {
+    #[error("{host} didn't present a certificate")]
+    Missing { host: String },
+    #[error("The certificate from {host} has no bridge id")]
+    NoBridgeId { host: String },
+    #[error("Expected bridge {expected} at {host}, but it's bridge {actual}")]
+    WrongBridge {
+        host: String,
+        expected: String,
+        actual: String,
+    },
+    #[error(
+        "The certificate from {host} has fingerprint {actual}, but {expected} is pinned. If the bridge was reset or \
+         replaced, run `hoo bridge forget {host}` and connect again"
+    )]
+    FingerprintMismatch {
+        host: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Couldn't remember the certificate from {host}: {message}")]
+    Store { host: String, message: String },
+}
+
+/// Hex encoded SHA-256 of a DER certificate
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Makes fingerprints copied from elsewhere, like `openssl x509 -fingerprint`, comparable with `fingerprint`
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.replace(':', "").to_lowercase()
+}
+
+/// A hyper client for talking to a bridge over HTTP or HTTPS
+pub fn client(trust: BridgeTrust) -> hyper::Client<BridgeConnector> {
+    hyper::Client::builder().build(BridgeConnector::new(trust))
+}
+
+/// Certificate problems come out of hyper as connection errors, which are worth telling apart from the bridge just
+/// not being there
+pub fn request_error(error: hyper::Error) -> HueError {
+    let certificate_error = std::error::Error::source(&error)
+        .and_then(|source| source.downcast_ref::<CertificateError>())
+        .cloned();
+
+    match certificate_error {
+        Some(certificate_error) => HueError::Certificate(certificate_error),
+        None => HueError::Network(error),
+    }
+}
+
+/// Connects over plain TCP for `http` URIs, and over TLS with a pinned bridge certificate for `https` ones
+#[derive(Debug, Clone)]
+pub struct BridgeConnector {
+    http: HttpConnector,
+    trust: Arc<BridgeTrust>,
+    /// Certificates already checked, by host, so the known bridges file isn't read for every connection
+    pinned: Arc<Mutex<HashMap<String, KnownBridge>>>,
+}
+
+impl BridgeConnector {
+    pub fn new(trust: BridgeTrust) -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+
+        Self {
+            http,
+            trust: Arc::new(trust),
+            pinned: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Service<Uri> for BridgeConnector {
+    type Response = BridgeStream;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let mut http = self.http.clone();
+        let verifier = Arc::new(PinningVerifier {
+            host: uri.authority().map(|authority| authority.as_str().to_string()).unwrap_or_default(),
+            trust: self.trust.clone(),
+            pinned: self.pinned.clone(),
+            failure: Mutex::new(None),
+        });
+
+        Box::pin(async move {
+            let https = uri.scheme_str() == Some("https");
+            let tcp = http.call(uri).await?;
+            if !https {
+                return Ok(BridgeStream::Http(tcp));
+            }
+
+            let mut config = ClientConfig::new();
+            config.enable_sni = false;
+            config.dangerous().set_certificate_verifier(verifier.clone());
+            let server_name = DNSNameRef::try_from_ascii_str(SERVER_NAME).expect("valid server name");
+
+            match TlsConnector::from(Arc::new(config)).connect(server_name, tcp).await {
+                Ok(tls) => Ok(BridgeStream::Https(Box::new(tls))),
+                // Hand back why the certificate was rejected rather than the TLS alert it turned into
+                Err(e) => match verifier.failure.lock().unwrap().take() {
+                    Some(failure) => Err(failure.into()),
+                    None => Err(e.into()),
+                },
+            }
+        })
+    }
+}
+
+/// Checks the certificate of one connection
+struct PinningVerifier {
+    host: String,
+    trust: Arc<BridgeTrust>,
+    pinned: Arc<Mutex<HashMap<String, KnownBridge>>>,
+    failure: Mutex<Option<CertificateError>>,
+}
+
+impl PinningVerifier {
+    fn verify(&self, certificates: &[Certificate]) -> std::result::Result<(), CertificateError> {
+        let host = self.host.clone();
+        let certificate = certificates.first().ok_or_else(|| CertificateError::Missing { host: host.clone() })?;
+        let actual = KnownBridge {
+            bridge_id: common_name(&certificate.0)
+                .ok_or_else(|| CertificateError::NoBridgeId { host: host.clone() })?
+                .to_lowercase(),
+            fingerprint: fingerprint(&certificate.0),
+        };
+
+        if let Some(expected) = &self.trust.bridge_id {
+            if !expected.eq_ignore_ascii_case(&actual.bridge_id) {
+                return Err(CertificateError::WrongBridge {
+                    host,
+                    expected: expected.clone(),
+                    actual: actual.bridge_id,
+                });
+            }
+        }
+
+        if let Some(expected) = &self.trust.fingerprint {
+            if normalize_fingerprint(expected) != actual.fingerprint {
+                return Err(CertificateError::FingerprintMismatch {
+                    host,
+                    expected: expected.clone(),
+                    actual: actual.fingerprint,
+                });
+            }
+            return Ok(());
+        }
+
+        let mut pinned = self.pinned.lock().unwrap();
+        let known = match pinned.get(&host) {
+            Some(known) => Some(known.clone()),
+            None => self.load_known(&host)?,
+        };
+
+        match known {
+            Some(known) if known.fingerprint != actual.fingerprint => Err(CertificateError::FingerprintMismatch {
+                host,
+                expected: known.fingerprint,
+                actual: actual.fingerprint,
+            }),
+            Some(known) => {
+                pinned.insert(host, known);
+                Ok(())
+            }
+            None => {
+                if let Some(known_bridges) = &self.trust.known_bridges {
+                    known_bridges.save(&host, actual.clone()).map_err(|e| CertificateError::Store {
+                        host: host.clone(),
+                        message: e.to_string(),
+                    })?;
+                }
+                pinned.insert(host, actual);
+                Ok(())
+            }
+        }
+    }
+
+    fn load_known(&self, host: &str) -> std::result::Result<Option<KnownBridge>, CertificateError> {
+        match &self.trust.known_bridges {
+            Some(known_bridges) => known_bridges.get(host).map_err(|e| CertificateError::Store {
+                host: host.to_string(),
+                message: e.to_string(),
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> std::result::Result<ServerCertVerified, TLSError> {
+        match self.verify(presented_certs) {
+            Ok(()) => Ok(ServerCertVerified::assertion()),
+            Err(e) => {
+                let message = e.to_string();
+                *self.failure.lock().unwrap() = Some(e);
+                Err(TLSError::General(message))
+            }
+        }
+    }
+}
+
+/// The subject common name of a DER certificate, which for a bridge is its id
+fn common_name(der: &[u8]) -> Option<String> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    let common_name = certificate.subject().iter_common_name().next()?;
+    common_name.as_str().ok().map(str::to_string)
+}
+
+/// A connection to a bridge, over TLS or not
+pub enum BridgeStream {
+    Http(TcpStream),
+    Https(Box<TlsStream<TcpStream>>),
+}
+
+impl Connection for BridgeStream {
+    fn connected(&self) -> Connected {
+        match self {
+            BridgeStream::Http(stream) => stream.connected(),
+            BridgeStream::Https(stream) => stream.get_ref().0.connected(),
+        }
+    }
+}
+
+impl AsyncRead for BridgeStream {
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [MaybeUninit<u8>]) -> bool {
+        match self {
+            BridgeStream::Http(stream) => stream.prepare_uninitialized_buffer(buf),
+            BridgeStream::Https(stream) => stream.prepare_uninitialized_buffer(buf),
+        }
+    }
+
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            BridgeStream::Http(stream) => Pin::new(stream).poll_read(cx, buf),
+            BridgeStream::Https(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for BridgeStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            BridgeStream::Http(stream) => Pin::new(stream).poll_write(cx, buf),
+            BridgeStream::Https(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            BridgeStream::Http(stream) => Pin::new(stream).poll_flush(cx),
+            BridgeStream::Https(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            BridgeStream::Http(stream) => Pin::new(stream).poll_shutdown(cx),
+            BridgeStream::Https(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{broadcast, oneshot, watch};
+
+use hoo_api_types::{LightCollection, LightColorMode, LightNumber};
+
+use crate::LightBackend;
+
+const EVENT_CAPACITY: usize = 256;
+
+/// A change noticed between two polls of the bridge
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum LightEvent {
+    Added { light: LightNumber, name: String },
+    Removed { light: LightNumber },
+    Power { light: LightNumber, on: bool },
+    Brightness { light: LightNumber, bri: u8 },
+    Color {
+        light: LightNumber,
+        colormode: Option<LightColorMode>,
+        hue: Option<u16>,
+        sat: Option<u8>,
+        xy: Option<(f32, f32)>,
+        ct: Option<u16>,
+    },
+    Reachability { light: LightNumber, reachable: bool },
+}
+
+impl LightEvent {
+    pub fn light(&self) -> LightNumber {
+        match self {
+            LightEvent::Added { light, .. }
+            | LightEvent::Removed { light }
+            | LightEvent::Power { light, .. }
+            | LightEvent::Brightness { light, .. }
+            | LightEvent::Color { light, .. }
+            | LightEvent::Reachability { light, .. } => *light,
+        }
+    }
+}
+
+/// Compares two snapshots of the lights. Events are ordered by light number.
+pub fn diff_lights(previous: &LightCollection, current: &LightCollection) -> Vec<LightEvent> {
+    let mut light_nums: Vec<LightNumber> = previous.keys().chain(current.keys()).cloned().collect();
+    light_nums.sort_unstable();
+    light_nums.dedup();
+
+    let mut events = Vec::new();
+    for light in light_nums {
+        let (old, new) = match (previous.get(&light), current.get(&light)) {
+            (Some(old), Some(new)) => (&old.state, &new.state),
+            (None, Some(new)) => {
+                events.push(LightEvent::Added { light, name: new.name.clone() });
+                continue;
+            }
+            (Some(_), None) => {
+                events.push(LightEvent::Removed { light });
+                continue;
+            }
+            (None, None) => continue,
+        };
+
+        if let (Some(reachable), true) = (new.reachable, new.reachable != old.reachable) {
+            events.push(LightEvent::Reachability { light, reachable });
+        }
+        if let (Some(on), true) = (new.on, new.on != old.on) {
+            events.push(LightEvent::Power { light, on });
+        }
+        if let (Some(bri), true) = (new.bri, new.bri != old.bri) {
+            events.push(LightEvent::Brightness { light, bri });
+        }
+        let color_changed = new.colormode != old.colormode
+            || new.hue != old.hue
+            || new.sat != old.sat
+            || new.xy != old.xy
+            || new.ct != old.ct;
+        if color_changed {
+            events.push(LightEvent::Color {
+                light,
+                colormode: new.colormode,
+                hue: new.hue,
+                sat: new.sat,
+                xy: new.xy,
+                ct: new.ct,
+            });
+        }
+    }
+
+    events
+}
+
+/// Polls the bridge in the background and broadcasts what changed. Polling stops when the watcher is dropped.
+/// Failed polls are skipped, so changes are reported against the last successful one.
+#[derive(Debug)]
+pub struct LightWatcher {
+    sender: broadcast::Sender<LightEvent>,
+    latest: watch::Receiver<Option<LightCollection>>,
+    _stop: oneshot::Sender<()>,
+}
+
+impl LightWatcher {
+    /// Must be called from within a tokio runtime
+    pub fn spawn<B: LightBackend>(backend: B, interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        let (stop, stopped) = oneshot::channel();
+        let (polled, latest) = watch::channel(None);
+
+        tokio::spawn(poll(backend, interval, sender.clone(), polled, stopped));
+
+        Self {
+            sender,
+            latest,
+            _stop: stop,
+        }
+    }
+
+    /// Receives every event sent after subscribing. Slow receivers skip ahead with a `Lagged` error.
+    pub fn subscribe(&self) -> broadcast::Receiver<LightEvent> {
+        self.sender.subscribe()
+    }
+
+    /// The lights as of the last successful poll
+    pub fn latest(&self) -> Option<LightCollection> {
+        self.latest.borrow().clone()
+    }
+
+    /// Receives the lights after every successful poll, changed or not, starting with the latest
+    pub fn polls(&self) -> watch::Receiver<Option<LightCollection>> {
+        self.latest.clone()
+    }
+}
+
+async fn poll<B: LightBackend>(
+    backend: B,
+    interval: Duration,
+    sender: broadcast::Sender<LightEvent>,
+    polled: watch::Sender<Option<LightCollection>>,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(interval);
+    let mut previous = None;
+
+    loop {
+        interval.tick().await;
+        if stopped.try_recv() != Err(oneshot::error::TryRecvError::Empty) {
+            return;
+        }
+
+        let current = match backend.get_all_lights().await {
+            Ok(lights) => lights,
+            Err(_) => continue,
+        };
+
+        // Update the latest lights before sending events, so a new subscriber's snapshot never misses one. This only
+        // fails once the watcher is dropped, which also stops polling.
+        let _ = polled.broadcast(Some(current.clone()));
+        if let Some(previous) = previous.replace(current.clone()) {
+            for event in diff_lights(&previous, &current) {
+                // Only fails when nobody is subscribed
+                let _ = sender.send(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hoo_api_types::{Light, LightState};
+
+    use super::*;
+
+    fn lights(states: &[(LightNumber, LightState)]) -> LightCollection {
+        states
+            .iter()
+            .map(|(light_num, state)| {
+                let light = Light {
+                    name: format!("Light {}", light_num),
+                    state: LightState {
+                        reachable: Some(true),
+                        ..state.clone()
+                    },
+                };
+                (*light_num, light)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nothing_changed() {
+        let before = lights(&[(1, LightState::new().on(true).bri(100))]);
+        assert!(diff_lights(&before, &before.clone()).is_empty());
+    }
+
+    #[test]
+    fn reports_each_change_in_light_order() {
+        let before = lights(&[
+            (1, LightState::new().on(false).bri(100)),
+            (2, LightState::new().on(true).ct(300)),
+            (3, LightState::new().on(true)),
+        ]);
+        let mut after = lights(&[
+            (4, LightState::new().on(true)),
+            (2, LightState::new().on(true).ct(400)),
+            (1, LightState::new().on(true).bri(50)),
+        ]);
+        after.get_mut(&2).unwrap().state.reachable = Some(false);
+
+        assert_eq!(
+            diff_lights(&before, &after),
+            vec![
+                LightEvent::Power { light: 1, on: true },
+                LightEvent::Brightness { light: 1, bri: 50 },
+                LightEvent::Reachability { light: 2, reachable: false },
+                LightEvent::Color {
+                    light: 2,
+                    colormode: None,
+                    hue: None,
+                    sat: None,
+                    xy: None,
+                    ct: Some(400),
+                },
+                LightEvent::Removed { light: 3 },
+                LightEvent::Added {
+                    light: 4,
+                    name: "Light 4".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_attributes_that_went_missing() {
+        let before = lights(&[(1, LightState::new().on(true).bri(100))]);
+        let after = lights(&[(1, LightState::new())]);
+        assert!(diff_lights(&before, &after).is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+
+use hoo_api_types::{LightNumber, LightState};
+
+use crate::error::Result;
+use crate::response::StateChangeResult;
+use crate::LightBackend;
+
+/// Roughly how many light commands a bridge handles per second before it starts dropping them
+pub const BRIDGE_COMMANDS_PER_SECOND: u32 = 10;
+
+/// How many lights can be waiting for a write before new ones are dropped
+pub const DEFAULT_MAX_PENDING: usize = 64;
+
+/// What happened to a state change sent through a [`WriteQueue`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "lowercase")]
+pub enum WriteOutcome {
+    Sent(StateChangeResult),
+    /// Combined with a later change to the same light before it was sent
+    Merged,
+    /// The queue was full or shut down
+    Dropped,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WriteQueueStats {
+    pub sent: u64,
+    pub merged: u64,
+    pub dropped: u64,
+    pub pending: usize,
+}
+
+struct Write {
+    light_num: LightNumber,
+    state: LightState,
+    reply: oneshot::Sender<Result<WriteOutcome>>,
+}
+
+struct Pending {
+    state: LightState,
+    reply: oneshot::Sender<Result<WriteOutcome>>,
+}
+
+/// Sends light state changes to the bridge no faster than a fixed rate. While a change is waiting, later changes
+/// to the same light are folded into it with [`merge`], so the light ends up where sending both would have left it.
+/// The queue shuts down once every handle to it is dropped.
+#[derive(Debug, Clone)]
+pub struct WriteQueue {
+    sender: mpsc::UnboundedSender<Write>,
+    stats: Arc<Mutex<WriteQueueStats>>,
+}
+
+impl WriteQueue {
+    /// Must be called from within a tokio runtime
+    pub fn spawn<B: LightBackend>(backend: B, commands_per_second: u32, max_pending: usize) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let stats = Arc::new(Mutex::new(WriteQueueStats::default()));
+        let interval = Duration::from_secs(1) / commands_per_second.max(1);
+
+        tokio::spawn(run(backend, receiver, stats.clone(), interval, max_pending));
+
+        Self { sender, stats }
+    }
+
+    /// Queues the change straight away. The returned future only needs to be awaited to find out what happened to it.
+    pub fn set_state(&self, light_num: LightNumber, state: &LightState) -> impl Future<Output = Result<WriteOutcome>> {
+        let (reply, outcome) = oneshot::channel();
+        let write = Write {
+            light_num,
+            state: state.clone(),
+            reply,
+        };
+
+        if self.sender.send(write).is_err() {
+            self.stats.lock().unwrap().dropped += 1;
+        }
+
+        async move { outcome.await.unwrap_or(Ok(WriteOutcome::Dropped)) }
+    }
+
+    pub fn stats(&self) -> WriteQueueStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+async fn run<B: LightBackend>(
+    backend: B,
+    mut receiver: mpsc::UnboundedReceiver<Write>,
+    stats: Arc<Mutex<WriteQueueStats>>,
+    interval: Duration,
+    max_pending: usize,
+) {
+    let mut order: VecDeque<LightNumber> = VecDeque::new();
+    let mut pending: HashMap<LightNumber, Pending> = HashMap::new();
+
+    loop {
+        if order.is_empty() {
+            match receiver.recv().await {
+                Some(write) => enqueue(write, &mut order, &mut pending, &stats, max_pending),
+                None => return,
+            }
+        }
+        while let Ok(write) = receiver.try_recv() {
+            enqueue(write, &mut order, &mut pending, &stats, max_pending);
+        }
+
+        let next = order.pop_front().and_then(|light_num| pending.remove(&light_num).map(|p| (light_num, p)));
+        let (light_num, Pending { state, reply }) = match next {
+            Some(next) => next,
+            None => continue,
+        };
+        stats.lock().unwrap().pending = order.len();
+
+        let result = backend.set_state(light_num, &state).await;
+        if result.is_ok() {
+            stats.lock().unwrap().sent += 1;
+        }
+        // The caller may not be waiting for the outcome
+        let _ = reply.send(result.map(WriteOutcome::Sent));
+
+        tokio::time::delay_for(interval).await;
+    }
+}
+
+fn enqueue(
+    write: Write,
+    order: &mut VecDeque<LightNumber>,
+    pending: &mut HashMap<LightNumber, Pending>,
+    stats: &Mutex<WriteQueueStats>,
+    max_pending: usize,
+) {
+    let mut stats = stats.lock().unwrap();
+
+    if let Some(existing) = pending.get_mut(&write.light_num) {
+        existing.state = merge(&existing.state, &write.state);
+        let merged = std::mem::replace(&mut existing.reply, write.reply);
+        let _ = merged.send(Ok(WriteOutcome::Merged));
+        stats.merged += 1;
+    } else if pending.len() >= max_pending {
+        let _ = write.reply.send(Ok(WriteOutcome::Dropped));
+        stats.dropped += 1;
+    } else {
+        order.push_back(write.light_num);
+        pending.insert(write.light_num, Pending {
+            state: write.state,
+            reply: write.reply,
+        });
+    }
+
+    stats.pending = order.len();
+}
+
+/// Folds `later` into a change that's still waiting to be sent. Later values win, except for increments: two
+/// increments add up, an increment after an absolute value moves that value, and an absolute value replaces any
+/// increment before it. Results are kept within what the bridge accepts.
+pub fn merge(pending: &LightState, later: &LightState) -> LightState {
+    let mut merged = LightState::combine(pending, later);
+
+    (merged.bri, merged.bri_inc) = merge_field(
+        (pending.bri, pending.bri_inc),
+        (later.bri, later.bri_inc),
+        |bri, inc| (i32::from(bri) + i32::from(inc)).clamp(1, 254) as u8,
+        |a, b| a.saturating_add(b).clamp(-254, 254),
+    );
+    (merged.sat, merged.sat_inc) = merge_field(
+        (pending.sat, pending.sat_inc),
+        (later.sat, later.sat_inc),
+        |sat, inc| (i32::from(sat) + i32::from(inc)).clamp(0, 254) as u8,
+        |a, b| a.saturating_add(b).clamp(-254, 254),
+    );
+    // Hue goes round in a circle, so it wraps rather than stopping at the ends
+    (merged.hue, merged.hue_inc) = merge_field(
+        (pending.hue, pending.hue_inc),
+        (later.hue, later.hue_inc),
+        |hue, inc| (i32::from(hue) + inc).rem_euclid(65536) as u16,
+        |a, b| a.saturating_add(b).clamp(-65534, 65534),
+    );
+    (merged.ct, merged.ct_inc) = merge_field(
+        (pending.ct, pending.ct_inc),
+        (later.ct, later.ct_inc),
+        |ct, inc| (i32::from(ct) + inc).clamp(153, 500) as u16,
+        |a, b| a.saturating_add(b).clamp(-65534, 65534),
+    );
+    (merged.xy, merged.xy_inc) = merge_field(
+        (pending.xy, pending.xy_inc),
+        (later.xy, later.xy_inc),
+        |(x, y), (x_inc, y_inc)| ((x + x_inc).clamp(0.0, 1.0), (y + y_inc).clamp(0.0, 1.0)),
+        |(x, y), (x_inc, y_inc)| ((x + x_inc).clamp(-0.5, 0.5), (y + y_inc).clamp(-0.5, 0.5)),
+    );
+
+    merged
+}
+
+/// Merges one attribute given as an absolute value and an increment, never leaving both set
+fn merge_field<T, I>(
+    pending: (Option<T>, Option<I>),
+    later: (Option<T>, Option<I>),
+    apply: impl Fn(T, I) -> T,
+    add: impl Fn(I, I) -> I,
+) -> (Option<T>, Option<I>) {
+    match (pending, later) {
+        (_, (Some(value), _)) => (Some(value), None),
+        ((Some(value), _), (None, Some(inc))) => (Some(apply(value, inc)), None),
+        ((None, Some(pending_inc)), (None, Some(inc))) => (None, Some(add(pending_inc, inc))),
+        ((value, pending_inc), (None, None)) => (value, pending_inc),
+        ((None, None), (None, inc)) => (None, inc),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bri_inc(inc: i16) -> LightState {
+        LightState {
+            bri_inc: Some(inc),
+            ..LightState::new()
+        }
+    }
+
+    /// Waiting writes, in the order they'll go out
+    #[derive(Default)]
+    struct Queue {
+        order: VecDeque<LightNumber>,
+        pending: HashMap<LightNumber, Pending>,
+        stats: Mutex<WriteQueueStats>,
+    }
+
+    impl Queue {
+        fn enqueue(
+            &mut self,
+            light_num: LightNumber,
+            state: LightState,
+            max_pending: usize,
+        ) -> oneshot::Receiver<Result<WriteOutcome>> {
+            let (reply, outcome) = oneshot::channel();
+            let write = Write { light_num, state, reply };
+            enqueue(write, &mut self.order, &mut self.pending, &self.stats, max_pending);
+            outcome
+        }
+
+        fn state(&self, light_num: LightNumber) -> &LightState {
+            &self.pending[&light_num].state
+        }
+    }
+
+    fn outcome(mut receiver: oneshot::Receiver<Result<WriteOutcome>>) -> Option<WriteOutcome> {
+        receiver.try_recv().ok().map(|outcome| outcome.unwrap())
+    }
+
+    #[test]
+    fn coalesces_writes_to_the_same_light() {
+        let mut queue = Queue::default();
+
+        let first = queue.enqueue(1, LightState::new().on(true), 10);
+        let _other = queue.enqueue(2, LightState::new().on(false), 10);
+        let second = queue.enqueue(1, bri_inc(10), 10);
+        let third = queue.enqueue(1, bri_inc(10), 10);
+
+        assert_eq!(queue.order, vec![1, 2]);
+        assert_eq!(queue.state(1).on, Some(true));
+        assert_eq!(queue.state(1).bri_inc, Some(20));
+        assert_eq!(outcome(first), Some(WriteOutcome::Merged));
+        assert_eq!(outcome(second), Some(WriteOutcome::Merged));
+        // Still waiting to be sent
+        assert_eq!(outcome(third), None);
+
+        let stats = *queue.stats.lock().unwrap();
+        assert_eq!((stats.merged, stats.dropped, stats.pending), (2, 0, 2));
+    }
+
+    #[test]
+    fn drops_new_lights_once_full() {
+        let mut queue = Queue::default();
+
+        let _first = queue.enqueue(1, LightState::new().on(true), 1);
+        let dropped = queue.enqueue(2, LightState::new().on(true), 1);
+        let _merged = queue.enqueue(1, LightState::new().bri(50), 1);
+
+        assert_eq!(queue.order, vec![1]);
+        assert_eq!(outcome(dropped), Some(WriteOutcome::Dropped));
+        assert_eq!(queue.state(1).bri, Some(50));
+
+        let stats = *queue.stats.lock().unwrap();
+        assert_eq!((stats.merged, stats.dropped, stats.pending), (1, 1, 1));
+    }
+
+    #[test]
+    fn increments_add_up() {
+        let merged = merge(&bri_inc(10), &bri_inc(10));
+        assert_eq!(merged.bri_inc, Some(20));
+        assert_eq!(merged.bri, None);
+
+        let merged = merge(&bri_inc(200), &bri_inc(200));
+        assert_eq!(merged.bri_inc, Some(254));
+    }
+
+    #[test]
+    fn absolute_values_replace_increments() {
+        let merged = merge(&bri_inc(10), &LightState::new().bri(50));
+        assert_eq!(merged.bri, Some(50));
+        assert_eq!(merged.bri_inc, None);
+        assert!(merged.validate().is_ok());
+    }
+
+    #[test]
+    fn increments_move_absolute_values() {
+        let merged = merge(&LightState::new().bri(100).on(true), &bri_inc(10));
+        assert_eq!(merged.bri, Some(110));
+        assert_eq!(merged.bri_inc, None);
+        assert_eq!(merged.on, Some(true));
+        assert!(merged.validate().is_ok());
+
+        let merged = merge(&LightState::new().bri(250), &bri_inc(10));
+        assert_eq!(merged.bri, Some(254));
+
+        let later = LightState {
+            hue_inc: Some(1000),
+            ..LightState::new()
+        };
+        assert_eq!(merge(&LightState::new().hue(65000), &later).hue, Some(464));
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use hoo_api::error::Result;
+use hoo_api::{
+    AppliedChange, Group, GroupAction, GroupAttributes, GroupCollection, GroupNumber, HueError, Light, LightBackend,
+    LightCollection, LightNumber, LightSelector, LightState, StateChangeResult,
+};
+
+/// Lights kept in memory, with no groups, to check that the provided methods only lean on the required ones
+#[derive(Clone, Default)]
+struct Simulated {
+    lights: Arc<Mutex<LightCollection>>,
+    writes: Arc<Mutex<Vec<LightNumber>>>,
+}
+
+impl Simulated {
+    /// Lights are reachable unless their state says otherwise
+    fn with_lights(lights: &[(LightNumber, &str, LightState)]) -> Self {
+        let backend = Self::default();
+        for (light_num, name, state) in lights {
+            let mut state = state.clone();
+            state.reachable = state.reachable.or(Some(true));
+            let light = Light {
+                name: name.to_string(),
+                state,
+            };
+            backend.lights.lock().unwrap().insert(*light_num, light);
+        }
+        backend
+    }
+
+    fn state(&self, light_num: LightNumber) -> LightState {
+        self.lights.lock().unwrap()[&light_num].state.clone()
+    }
+}
+
+fn unsupported() -> HueError {
+    HueError::UnexpectedResponse("groups aren't simulated".to_string())
+}
+
+#[async_trait]
+impl LightBackend for Simulated {
+    async fn get_all_lights(&self) -> Result<LightCollection> {
+        Ok(self.lights.lock().unwrap().clone())
+    }
+
+    async fn get_light(&self, light_num: LightNumber) -> Result<Light> {
+        self.lights
+            .lock()
+            .unwrap()
+            .get(&light_num)
+            .cloned()
+            .ok_or_else(|| HueError::UnexpectedResponse(format!("no light {}", light_num)))
+    }
+
+    async fn set_state(&self, light_num: LightNumber, state: &LightState) -> Result<StateChangeResult> {
+        let mut lights = self.lights.lock().unwrap();
+        let light = lights
+            .get_mut(&light_num)
+            .ok_or_else(|| HueError::UnexpectedResponse(format!("no light {}", light_num)))?;
+        self.writes.lock().unwrap().push(light_num);
+
+        let mut result = StateChangeResult::default();
+        if let Some(on) = state.on {
+            light.state.on = Some(on);
+            result.applied.push(AppliedChange {
+                address: format!("/lights/{}/state/on", light_num),
+                value: json!(on),
+            });
+        }
+        if let Some(bri) = state.bri {
+            light.state.bri = Some(bri);
+            result.applied.push(AppliedChange {
+                address: format!("/lights/{}/state/bri", light_num),
+                value: json!(bri),
+            });
+        }
+        Ok(result)
+    }
+
+    async fn get_all_groups(&self) -> Result<GroupCollection> {
+        Ok(GroupCollection::new())
+    }
+
+    async fn get_group(&self, _group_num: GroupNumber) -> Result<Group> {
+        Err(unsupported())
+    }
+
+    async fn create_group(&self, _attributes: &GroupAttributes) -> Result<GroupNumber> {
+        Err(unsupported())
+    }
+
+    async fn update_group(&self, _group_num: GroupNumber, _attributes: &GroupAttributes) -> Result<StateChangeResult> {
+        Err(unsupported())
+    }
+
+    async fn delete_group(&self, _group_num: GroupNumber) -> Result<StateChangeResult> {
+        Err(unsupported())
+    }
+
+    async fn set_group_action(&self, _group_num: GroupNumber, _action: &GroupAction) -> Result<StateChangeResult> {
+        Err(unsupported())
+    }
+}
+
+fn backend() -> Simulated {
+    Simulated::with_lights(&[
+        (1, "Desk", LightState::new().on(true).bri(100)),
+        (2, "Lamp", LightState::new().on(false).bri(100)),
+        (3, "Kitchen", LightState::new().on(true).bri(254)),
+    ])
+}
+
+#[tokio::test]
+async fn toggles_and_switches() {
+    let backend = backend();
+
+    backend.toggle(1).await.unwrap();
+    backend.on(2).await.unwrap();
+    backend.off(3).await.unwrap();
+
+    assert!(!backend.state(1).is_on());
+    assert!(backend.state(2).is_on());
+    assert!(!backend.state(3).is_on());
+}
+
+#[tokio::test]
+async fn selects_without_groups() {
+    let backend = backend();
+
+    let selector: LightSelector = "name:k*,1".parse().unwrap();
+    let mut selected: Vec<_> = backend.select_lights(&selector).await.unwrap().into_keys().collect();
+    selected.sort_unstable();
+    assert_eq!(selected, vec![1, 3]);
+
+    let mut active: Vec<_> = backend.get_active_lights().await.unwrap().into_keys().collect();
+    active.sort_unstable();
+    assert_eq!(active, vec![1, 3]);
+}
+
+#[tokio::test]
+async fn set_states_goes_in_order_and_past_failures() {
+    let backend = backend();
+
+    let states = vec![
+        (3, LightState::new().bri(1)),
+        (9, LightState::new().bri(1)),
+        (1, LightState::new().bri(1)),
+    ];
+    let results = backend.set_states(&states).await;
+
+    let light_nums: Vec<_> = results.iter().map(|(light_num, _)| *light_num).collect();
+    assert_eq!(light_nums, vec![3, 9, 1]);
+    assert!(results[1].1.is_err());
+    assert_eq!(*backend.writes.lock().unwrap(), vec![3, 1]);
+    assert_eq!(backend.state(1).bri, Some(1));
+}
+
+#[tokio::test]
+async fn captures_and_applies_scenes() {
+    let backend = backend();
+
+    let scene = backend.capture_scene("Evening", &[1, 3]).await.unwrap();
+    assert_eq!(scene.states.len(), 2);
+    backend.set_states(&[(1, LightState::new().bri(1)), (3, LightState::new().bri(1))]).await;
+
+    let result = backend.apply_scene(&scene, None).await.unwrap();
+    assert!(result.is_success());
+    assert_eq!(backend.state(1).bri, Some(100));
+    assert_eq!(backend.state(3).bri, Some(254));
+}
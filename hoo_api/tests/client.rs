@@ -0,0 +1,214 @@
+use std::time::{Duration, Instant};
+
+use hoo_api::{
+    BridgeErrorKind, GroupAction, GroupAttributes, HueClient, HueError, LightBackend, LightSelector, LightState,
+};
+use hoo_mock_bridge::{Failure, MockBridge, USER_ID};
+
+fn bridge() -> (MockBridge, HueClient) {
+    let bridge = MockBridge::with_sample_home();
+    let client = HueClient::new(&bridge.base_uri(), USER_ID);
+    (bridge, client)
+}
+
+#[tokio::test]
+async fn gets_lights() {
+    let (_bridge, client) = bridge();
+
+    let lights = client.get_all_lights().await.unwrap();
+    assert_eq!(lights.len(), 3);
+    assert_eq!(lights[&1].name, "Desk");
+
+    let light = client.get_light(2).await.unwrap();
+    assert_eq!(light.name, "Lamp");
+    assert!(!light.state.is_on());
+}
+
+#[tokio::test]
+async fn missing_light_is_a_bridge_error() {
+    let (_bridge, client) = bridge();
+
+    let error = client.get_light(9).await.unwrap_err();
+    assert_eq!(error.bridge_error_kind(), Some(BridgeErrorKind::ResourceNotAvailable));
+}
+
+#[tokio::test]
+async fn wrong_user_is_unauthorized() {
+    let (bridge, _) = bridge();
+    let client = HueClient::new(&bridge.base_uri(), "someone-else");
+
+    let error = client.get_all_lights().await.unwrap_err();
+    assert_eq!(error.bridge_error_kind(), Some(BridgeErrorKind::UnauthorizedUser));
+}
+
+#[tokio::test]
+async fn sets_state() {
+    let (bridge, client) = bridge();
+
+    let result = client.set_state(1, &LightState::new().bri(50).hue(1000)).await.unwrap();
+    assert!(result.is_success());
+    let mut attributes = result.applied_attributes();
+    attributes.sort_unstable();
+    assert_eq!(attributes, vec!["bri", "hue"]);
+
+    let light = bridge.light(1).unwrap();
+    assert_eq!(light.state.bri, Some(50));
+    assert_eq!(light.state.hue, Some(1000));
+}
+
+#[tokio::test]
+async fn can_only_turn_on_lights_that_are_off() {
+    let (bridge, client) = bridge();
+
+    let error = client.set_state(2, &LightState::new().bri(50)).await.unwrap_err();
+    assert_eq!(error.bridge_error_kind(), Some(BridgeErrorKind::DeviceIsOff));
+
+    let result = client.set_state(2, &LightState::new().on(true).bri(50)).await.unwrap();
+    assert!(result.is_success());
+    assert_eq!(bridge.light(2).unwrap().state.bri, Some(50));
+}
+
+#[tokio::test]
+async fn reports_attributes_that_fail() {
+    let (bridge, client) = bridge();
+
+    // The client doesn't validate, so out of range values reach the bridge
+    let result = client.set_state(1, &LightState::new().bri(80).sat(255)).await.unwrap();
+    assert_eq!(result.applied_attributes(), vec!["bri"]);
+    assert_eq!(result.failed.len(), 1);
+    assert_eq!(result.failed[0].kind, BridgeErrorKind::InvalidParameterValue);
+    assert_eq!(result.failed[0].address, "/lights/1/state/sat");
+    assert_eq!(bridge.light(1).unwrap().state.bri, Some(80));
+}
+
+#[tokio::test]
+async fn toggles() {
+    let (bridge, client) = bridge();
+
+    client.toggle(1).await.unwrap();
+    client.toggle(2).await.unwrap();
+
+    assert!(!bridge.light(1).unwrap().state.is_on());
+    assert!(bridge.light(2).unwrap().state.is_on());
+}
+
+#[tokio::test]
+async fn set_states_keeps_going_past_failures() {
+    let (bridge, client) = bridge();
+    bridge.set_reachable(3, false);
+
+    let states = vec![
+        (1, LightState::new().on(false)),
+        (3, LightState::new().on(false)),
+        (9, LightState::new().on(false)),
+    ];
+    let results = client.set_states(&states).await;
+
+    let light_nums: Vec<_> = results.iter().map(|(light_num, _)| *light_num).collect();
+    assert_eq!(light_nums, vec![1, 3, 9]);
+    assert!(results[0].1.is_ok());
+    assert_eq!(results[1].1.as_ref().unwrap_err().bridge_error_kind(), Some(BridgeErrorKind::DeviceUnreachable));
+    assert_eq!(results[2].1.as_ref().unwrap_err().bridge_error_kind(), Some(BridgeErrorKind::ResourceNotAvailable));
+    assert!(!bridge.light(1).unwrap().state.is_on());
+    assert!(bridge.light(3).unwrap().state.is_on());
+}
+
+#[tokio::test]
+async fn selects_lights() {
+    let (bridge, client) = bridge();
+
+    let selector: LightSelector = "name:k*,group:living room".parse().unwrap();
+    let mut selected: Vec<_> = client.select_lights(&selector).await.unwrap().into_keys().collect();
+    selected.sort_unstable();
+    assert_eq!(selected, vec![1, 2, 3]);
+
+    bridge.clear_requests();
+    let active: LightSelector = "active".parse().unwrap();
+    let mut selected: Vec<_> = client.select_lights(&active).await.unwrap().into_keys().collect();
+    selected.sort_unstable();
+    assert_eq!(selected, vec![1, 3]);
+    // Groups are only fetched for selectors that need them
+    let paths: Vec<_> = bridge.requests().into_iter().map(|request| request.path).collect();
+    assert_eq!(paths, vec!["/lights"]);
+}
+
+#[tokio::test]
+async fn manages_groups() {
+    let (bridge, client) = bridge();
+
+    let group_num = client
+        .create_group(&GroupAttributes::new().name("Everything").lights(&[1, 2, 3]))
+        .await
+        .unwrap();
+    assert_eq!(group_num, 2);
+    assert_eq!(client.get_group(group_num).await.unwrap().lights, vec![1, 2, 3]);
+
+    client
+        .update_group(group_num, &GroupAttributes::new().name("Some things").lights(&[2]))
+        .await
+        .unwrap();
+    let group = bridge.group(group_num).unwrap();
+    assert_eq!(group.name, "Some things");
+    assert_eq!(group.lights, vec![2]);
+
+    client.delete_group(group_num).await.unwrap();
+    assert!(!client.get_all_groups().await.unwrap().contains_key(&group_num));
+}
+
+#[tokio::test]
+async fn group_action_sets_every_light_in_the_group() {
+    let (bridge, client) = bridge();
+
+    client.set_group_action(1, &GroupAction::from(LightState::new().on(true).bri(10))).await.unwrap();
+
+    for light_num in &[1, 2] {
+        let light = bridge.light(*light_num).unwrap();
+        assert!(light.state.is_on());
+        assert_eq!(light.state.bri, Some(10));
+    }
+    assert_eq!(bridge.light(3).unwrap().state.bri, Some(254));
+
+    let group = client.get_group(1).await.unwrap();
+    assert!(group.state.unwrap().all_on);
+}
+
+#[tokio::test]
+async fn applies_saved_scenes() {
+    let (bridge, client) = bridge();
+
+    let scene = client.capture_scene("Evening", &[1, 3]).await.unwrap();
+    client.set_states(&[(1, LightState::new().bri(1)), (3, LightState::new().bri(1))]).await;
+
+    let result = client.apply_scene(&scene, Some(5)).await.unwrap();
+    assert!(result.is_success());
+    assert_eq!(bridge.light(1).unwrap().state.bri, Some(100));
+    assert_eq!(bridge.light(3).unwrap().state.bri, Some(254));
+}
+
+#[tokio::test]
+async fn surfaces_injected_failures() {
+    let (bridge, client) = bridge();
+    bridge.fail_next(Failure::Bridge {
+        kind: 901,
+        description: "Internal error, 404".to_string(),
+    });
+    bridge.fail_next(Failure::Status(503));
+
+    let error = client.get_all_lights().await.unwrap_err();
+    assert_eq!(error.bridge_error_kind(), Some(BridgeErrorKind::InternalError));
+
+    let error = client.set_state(1, &LightState::new().bri(10)).await.unwrap_err();
+    assert!(matches!(error, HueError::ServerError(status) if status.as_u16() == 503), "{:?}", error);
+
+    assert!(client.get_all_lights().await.is_ok());
+}
+
+#[tokio::test]
+async fn waits_out_latency() {
+    let (bridge, client) = bridge();
+    bridge.set_latency(Duration::from_millis(100));
+
+    let started = Instant::now();
+    client.get_light(1).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(100));
+}
@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use hoo_api::{HueClient, HueError, LightBackend, LightState, RequestPolicy};
+use hoo_mock_bridge::{Failure, MockBridge, USER_ID};
+
+/// Gives up on each attempt after 50ms and retries quickly, so slow requests don't slow the tests down
+fn policy() -> RequestPolicy {
+    RequestPolicy {
+        timeout: Duration::from_millis(50),
+        retries: 2,
+        backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(20),
+        failure_threshold: 0,
+        cooldown: Duration::from_millis(200),
+    }
+}
+
+fn bridge(policy: RequestPolicy) -> (MockBridge, HueClient) {
+    let bridge = MockBridge::with_sample_home();
+    let client = HueClient::new(&bridge.base_uri(), USER_ID).with_policy(policy);
+    (bridge, client)
+}
+
+fn slow() -> Failure {
+    Failure::Slow(Duration::from_millis(500))
+}
+
+#[tokio::test]
+async fn times_out() {
+    let (bridge, client) = bridge(RequestPolicy { retries: 0, ..policy() });
+    bridge.fail_next(slow());
+
+    let started = Instant::now();
+    let error = client.get_all_lights().await.unwrap_err();
+    assert!(matches!(error, HueError::Timeout(timeout) if timeout == Duration::from_millis(50)), "{:?}", error);
+    assert!(started.elapsed() < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn retries_reads() {
+    let (bridge, client) = bridge(policy());
+    bridge.fail_next(slow());
+    bridge.fail_next(slow());
+
+    assert_eq!(client.get_all_lights().await.unwrap().len(), 3);
+    assert_eq!(bridge.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_the_last_retry() {
+    let (bridge, client) = bridge(policy());
+    for _ in 0..3 {
+        bridge.fail_next(slow());
+    }
+
+    let error = client.get_light(1).await.unwrap_err();
+    assert!(matches!(error, HueError::Timeout(_)), "{:?}", error);
+    assert_eq!(bridge.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn never_retries_changes() {
+    let (bridge, client) = bridge(policy());
+    bridge.fail_next(slow());
+
+    let state = LightState {
+        bri_inc: Some(10),
+        ..LightState::new()
+    };
+    let error = client.set_state(1, &state).await.unwrap_err();
+    assert!(matches!(error, HueError::Timeout(_)), "{:?}", error);
+    assert_eq!(bridge.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn times_out_reading_the_body() {
+    let (bridge, client) = bridge(RequestPolicy { retries: 0, ..policy() });
+    bridge.fail_next(Failure::SlowBody(Duration::from_millis(500)));
+
+    let started = Instant::now();
+    let error = client.get_all_lights().await.unwrap_err();
+    assert!(matches!(error, HueError::Timeout(_)), "{:?}", error);
+    assert!(started.elapsed() < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn retries_reads_past_server_errors() {
+    let (bridge, client) = bridge(policy());
+    bridge.fail_next(Failure::Status(503));
+    bridge.fail_next(Failure::Status(500));
+
+    assert_eq!(client.get_all_lights().await.unwrap().len(), 3);
+    assert_eq!(bridge.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn unreachable_bridges_are_transient_errors() {
+    let (bridge, client) = bridge(policy());
+    drop(bridge);
+
+    let error = client.get_all_lights().await.unwrap_err();
+    assert!(matches!(error, HueError::Network(_)), "{:?}", error);
+    assert!(error.is_transient());
+}
+
+#[tokio::test]
+async fn fails_fast_once_the_bridge_keeps_failing() {
+    let (bridge, client) = bridge(RequestPolicy {
+        retries: 0,
+        failure_threshold: 2,
+        ..policy()
+    });
+    bridge.fail_next(slow());
+    bridge.fail_next(Failure::Status(502));
+
+    assert!(matches!(client.get_all_lights().await, Err(HueError::Timeout(_))));
+    assert!(matches!(client.get_all_lights().await, Err(HueError::ServerError(_))));
+    assert!(client.is_circuit_open());
+
+    bridge.clear_requests();
+    let error = client.clone().on(1).await.unwrap_err();
+    assert!(matches!(error, HueError::CircuitOpen { failures: 2, .. }), "{:?}", error);
+    assert!(bridge.requests().is_empty());
+
+    // Once the cooldown is over the bridge is tried again, and answering closes the breaker
+    tokio::time::delay_for(Duration::from_millis(250)).await;
+    assert!(client.get_all_lights().await.is_ok());
+    assert!(!client.is_circuit_open());
+}
+
+#[test]
+fn backoff_doubles_with_jitter_up_to_the_max() {
+    let policy = RequestPolicy {
+        backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(300),
+        ..RequestPolicy::default()
+    };
+
+    for _ in 0..20 {
+        let first = policy.backoff_delay(0);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100), "{:?}", first);
+        let second = policy.backoff_delay(1);
+        assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200), "{:?}", second);
+        let capped = policy.backoff_delay(40);
+        assert!(capped >= Duration::from_millis(150) && capped <= Duration::from_millis(300), "{:?}", capped);
+    }
+}
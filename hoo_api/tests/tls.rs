@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use hoo_api::{BridgeTrust, CertificateError, HueClient, HueError, KnownBridges, LightBackend};
+use hoo_mock_bridge::{MockBridge, MockCertificate, BRIDGE_ID, USER_ID};
+
+fn bridge(certificate: MockCertificate) -> MockBridge {
+    let bridge = MockBridge::start_https(certificate);
+    bridge.add_sample_home();
+    bridge
+}
+
+/// A known bridges file in the temp dir, removed when dropped
+struct TempKnownBridges(PathBuf);
+
+impl TempKnownBridges {
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("hoo_known_bridges_{}_{}.json", test, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        Self(path)
+    }
+
+    fn store(&self) -> KnownBridges {
+        KnownBridges::new(&self.0)
+    }
+
+    fn trust(&self) -> BridgeTrust {
+        BridgeTrust {
+            known_bridges: Some(self.store()),
+            ..BridgeTrust::default()
+        }
+    }
+}
+
+impl Drop for TempKnownBridges {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+fn certificate_error(error: HueError) -> CertificateError {
+    match error {
+        HueError::Certificate(error) => error,
+        error => panic!("expected a certificate error, got {:?}", error),
+    }
+}
+
+#[tokio::test]
+async fn trusts_and_remembers_the_first_certificate() {
+    let bridge = bridge(MockCertificate::Bridge);
+    let known_bridges = TempKnownBridges::new("first");
+
+    let client = HueClient::with_trust(&bridge.base_uri(), USER_ID, known_bridges.trust());
+    assert_eq!(client.get_all_lights().await.unwrap().len(), 3);
+
+    let known = known_bridges.store().get(&bridge.addr().to_string()).unwrap().unwrap();
+    assert_eq!(known.bridge_id, BRIDGE_ID);
+    assert_eq!(known.fingerprint.len(), 64);
+
+    // A new client checks against what was remembered
+    let client = HueClient::with_trust(&bridge.base_uri(), USER_ID, known_bridges.trust());
+    assert!(client.get_light(1).await.is_ok());
+}
+
+#[tokio::test]
+async fn rejects_a_changed_certificate() {
+    let bridge = bridge(MockCertificate::Bridge);
+    let replacement = self::bridge(MockCertificate::Replacement);
+    let known_bridges = TempKnownBridges::new("changed");
+
+    let client = HueClient::with_trust(&bridge.base_uri(), USER_ID, known_bridges.trust());
+    client.get_all_lights().await.unwrap();
+    // Pretend the replacement is the bridge that was seen before
+    let known = known_bridges.store().get(&bridge.addr().to_string()).unwrap().unwrap();
+    known_bridges.store().save(&replacement.addr().to_string(), known.clone()).unwrap();
+
+    let client = HueClient::with_trust(&replacement.base_uri(), USER_ID, known_bridges.trust());
+    let error = certificate_error(client.get_all_lights().await.unwrap_err());
+    assert!(
+        matches!(&error, CertificateError::FingerprintMismatch { expected, .. } if *expected == known.fingerprint),
+        "{:?}",
+        error
+    );
+    assert!(replacement.requests().is_empty());
+
+    // Forgetting the bridge trusts the new certificate
+    assert!(known_bridges.store().remove(&replacement.addr().to_string()).unwrap());
+    assert!(client.get_all_lights().await.is_ok());
+}
+
+#[tokio::test]
+async fn pins_a_configured_fingerprint() {
+    let bridge = bridge(MockCertificate::Bridge);
+    let known_bridges = TempKnownBridges::new("configured");
+    HueClient::with_trust(&bridge.base_uri(), USER_ID, known_bridges.trust())
+        .get_all_lights()
+        .await
+        .unwrap();
+    let fingerprint = known_bridges.store().get(&bridge.addr().to_string()).unwrap().unwrap().fingerprint;
+
+    // Written the way openssl prints them
+    let openssl_style = fingerprint
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).to_uppercase())
+        .collect::<Vec<_>>()
+        .join(":");
+    let trust = BridgeTrust {
+        fingerprint: Some(openssl_style),
+        ..BridgeTrust::default()
+    };
+    assert!(HueClient::with_trust(&bridge.base_uri(), USER_ID, trust.clone()).get_all_lights().await.is_ok());
+
+    let replacement = self::bridge(MockCertificate::Replacement);
+    let error = HueClient::with_trust(&replacement.base_uri(), USER_ID, trust)
+        .get_all_lights()
+        .await
+        .unwrap_err();
+    assert!(matches!(certificate_error(error), CertificateError::FingerprintMismatch { .. }));
+}
+
+#[tokio::test]
+async fn checks_the_bridge_id() {
+    let bridge = bridge(MockCertificate::Bridge);
+
+    let trust = BridgeTrust {
+        bridge_id: Some(BRIDGE_ID.to_uppercase()),
+        ..BridgeTrust::default()
+    };
+    assert!(HueClient::with_trust(&bridge.base_uri(), USER_ID, trust).get_all_lights().await.is_ok());
+
+    let trust = BridgeTrust {
+        bridge_id: Some("001788fffe000000".to_string()),
+        ..BridgeTrust::default()
+    };
+    let error = HueClient::with_trust(&bridge.base_uri(), USER_ID, trust)
+        .get_all_lights()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        certificate_error(error),
+        CertificateError::WrongBridge {
+            host: bridge.addr().to_string(),
+            expected: "001788fffe000000".to_string(),
+            actual: BRIDGE_ID.to_string(),
+        }
+    );
+}
+
+#[tokio::test]
+async fn new_clients_speak_https() {
+    let bridge = bridge(MockCertificate::Bridge);
+
+    let client = HueClient::new(&bridge.base_uri(), USER_ID);
+    client.on(2).await.unwrap();
+    assert!(client.get_light(2).await.unwrap().state.is_on());
+}
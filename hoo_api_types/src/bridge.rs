@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// The unauthenticated subset of `/api/config` every bridge exposes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeConfig {
+    pub name: String,
+    pub bridgeid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modelid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apiversion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swversion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+}
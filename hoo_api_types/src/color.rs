@@ -19,9 +19,9 @@ impl Color {
     }
 
     pub fn from_rgb(red: f64, green: f64, blue: f64) -> Self {
-        let r = red.min(1.0).max(0.0);
-        let g = green.min(1.0).max(0.0);
-        let b = blue.min(1.0).max(0.0);
+        let r = red.clamp(0.0, 1.0);
+        let g = green.clamp(0.0, 1.0);
+        let b = blue.clamp(0.0, 1.0);
 
         let cmax = r.max(g).max(b);
         let cmin = r.min(g).min(b);
@@ -48,9 +48,9 @@ impl Color {
 
         let value = cmax;
 
-        let h = ((hue / 360.0) * f64::from(std::u16::MAX)) as u16;
-        let s = (saturation * f64::from(std::u8::MAX)) as u8;
-        let v = (value * f64::from(std::u8::MAX)) as u8;
+        let h = ((hue / 360.0) * f64::from(u16::MAX)) as u16;
+        let s = (saturation * f64::from(u8::MAX)) as u8;
+        let v = (value * f64::from(u8::MAX)) as u8;
 
         Color::from_hsv(h, s, v)
     }
@@ -60,16 +60,16 @@ impl Color {
     }
 
     pub fn rgb(&self) -> (f64, f64, f64) {
-        let h = (f64::from(self.hue) / f64::from(std::u16::MAX)) * 360.0;
-        let s = f64::from(self.saturation) / f64::from(std::u8::MAX);
-        let v = f64::from(self.value) / f64::from(std::u8::MAX);
+        let h = (f64::from(self.hue) / f64::from(u16::MAX)) * 360.0;
+        let s = f64::from(self.saturation) / f64::from(u8::MAX);
+        let v = f64::from(self.value) / f64::from(u8::MAX);
 
         let c = v * s;
         let hp = h / 60.0;
         let x = c * (1.0 - ((hp % 2.0) - 1.0).abs());
 
         let (r, g, b) = match hp {
-            hp if hp >= 0.0 && hp <= 1.0 => (c, x, 0.0),
+            hp if (0.0..=1.0).contains(&hp) => (c, x, 0.0),
             hp if hp > 1.0 && hp <= 2.0 => (x, c, 0.0),
             hp if hp > 2.0 && hp <= 3.0 => (0.0, c, x),
             hp if hp > 3.0 && hp <= 4.0 => (0.0, x, c),
@@ -97,5 +97,5 @@ impl Display for Color {
 
 pub fn deg_to_u16(deg: f64) -> u16 {
     let multiplier = deg / 360.0;
-    (multiplier * f64::from(std::u16::MAX)) as u16
+    (multiplier * f64::from(u16::MAX)) as u16
 }
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::light::{LightNumber, LightState};
+use crate::string_numbers::light_numbers;
+
+pub type GroupNumber = u8;
+pub type GroupCollection = HashMap<GroupNumber, Group>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    #[serde(with = "light_numbers")]
+    pub lights: Vec<LightNumber>,
+    #[serde(rename = "type")]
+    pub group_type: GroupType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub action: GroupAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<GroupState>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct GroupState {
+    pub all_on: bool,
+    pub any_on: bool,
+}
+
+/// The body of a `groups/{id}/action` PUT. Takes the same attributes as a light's state plus a scene to recall.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupAction {
+    #[serde(flatten)]
+    pub state: LightState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scene: Option<String>,
+}
+
+impl GroupAction {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn scene(mut self, scene_id: &str) -> Self {
+        self.scene = Some(scene_id.to_string());
+        self
+    }
+}
+
+impl From<LightState> for GroupAction {
+    fn from(state: LightState) -> GroupAction {
+        GroupAction {
+            state,
+            scene: None,
+        }
+    }
+}
+
+/// Attributes for creating or updating a group. Unset attributes are left alone.
+/// The type can only be set when the group is created.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupAttributes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "light_numbers")]
+    pub lights: Vec<LightNumber>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub group_type: Option<GroupType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+}
+
+impl GroupAttributes {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn lights(mut self, lights: &[LightNumber]) -> Self {
+        self.lights = lights.to_vec();
+        self
+    }
+
+    pub fn group_type(mut self, group_type: GroupType) -> Self {
+        self.group_type = Some(group_type);
+        self
+    }
+
+    pub fn class(mut self, class: &str) -> Self {
+        self.class = Some(class.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupType {
+    LightGroup,
+    Room,
+    Zone,
+    Entertainment,
+    Luminaire,
+    #[serde(rename = "LightSource")]
+    Lightsource,
+    #[serde(other)]
+    Other,
+}
+
+impl FromStr for GroupType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lightgroup" => Ok(GroupType::LightGroup),
+            "room" => Ok(GroupType::Room),
+            "zone" => Ok(GroupType::Zone),
+            "entertainment" => Ok(GroupType::Entertainment),
+            _ => Err(format!("Unknown group type: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_types_use_the_bridge_names() {
+        let group_type: GroupType = serde_json::from_str(r#""LightSource""#).unwrap();
+        assert_eq!(group_type, GroupType::Lightsource);
+        assert_eq!(serde_json::to_string(&group_type).unwrap(), r#""LightSource""#);
+
+        let group_type: GroupType = serde_json::from_str(r#""Something new""#).unwrap();
+        assert_eq!(group_type, GroupType::Other);
+    }
+}
@@ -1,5 +1,21 @@
+pub mod bridge;
 pub mod color;
+pub mod group;
 pub mod light;
+pub mod rule;
+pub mod scene;
+pub mod schedule;
+pub mod selector;
+pub mod sensor;
 
+mod string_numbers;
+
+pub use self::bridge::BridgeConfig;
 pub use self::color::Color;
-pub use self::light::{LightNumber, LightCollection, LightState, LightStateQuery, LightEffect, LightAlert, LightColorMode, Light};
\ No newline at end of file
+pub use self::group::{GroupNumber, GroupCollection, Group, GroupAction, GroupAttributes, GroupState, GroupType};
+pub use self::light::{LightNumber, LightCollection, LightState, LightStateError, LightStateQuery, LightEffect, LightAlert, LightColorMode, Light, MAX_SAT};
+pub use self::rule::{RuleId, RuleCollection, Rule, RuleStatus, RuleError, Condition, Operator, Action, ActionMethod};
+pub use self::scene::{SceneId, SceneCollection, Scene, SceneAttributes, SceneType, SavedScene};
+pub use self::schedule::{ScheduleId, ScheduleCollection, Schedule, ScheduleCommand, ScheduleStatus};
+pub use self::selector::{LightSelector, SelectorError};
+pub use self::sensor::{SensorNumber, SensorCollection, Sensor, SensorType, SensorState, SensorConfig};
\ No newline at end of file
@@ -1,4 +1,7 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
 
 use serde::{Deserialize, Serialize};
 
@@ -7,6 +10,9 @@ use crate::color::Color;
 pub type LightNumber = u8;
 pub type LightCollection = HashMap<LightNumber, Light>;
 
+/// The most saturated a light gets. Bridges reject 255.
+pub const MAX_SAT: u8 = 254;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Light {
     pub name: String,
@@ -27,7 +33,7 @@ impl Light {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LightState {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub on: Option<bool>,
@@ -132,17 +138,11 @@ impl LightState {
     }
 
     pub fn is_on(&self) -> bool {
-        match self.on {
-            Some(is_on) => is_on,
-            None => false,
-        }
+        self.on.unwrap_or_default()
     }
 
     pub fn is_reachable(&self) -> bool {
-        match self.reachable {
-            Some(is_reachable) => is_reachable,
-            None => false,
-        }
+        self.reachable.unwrap_or_default()
     }
 
     pub fn get_color(&self) -> Option<Color> {
@@ -153,6 +153,82 @@ impl LightState {
         }
     }
 
+    /// The attributes needed to restore this state later. Color is taken from whichever
+    /// color mode is active, and lights that are off only get turned off.
+    pub fn snapshot(&self) -> LightState {
+        if !self.is_on() {
+            return LightState::new().on(false);
+        }
+
+        let state = LightState {
+            on: Some(true),
+            bri: self.bri,
+            ..LightState::default()
+        };
+
+        match self.colormode {
+            Some(LightColorMode::XY) => LightState { xy: self.xy, ..state },
+            Some(LightColorMode::CT) => LightState { ct: self.ct, ..state },
+            _ => LightState { hue: self.hue, sat: self.sat, ..state },
+        }
+    }
+
+    /// Checks a state before it's sent, using the ranges the bridge accepts
+    pub fn validate(&self) -> Result<(), LightStateError> {
+        if self.colormode.is_some() {
+            return Err(LightStateError::ReadOnly("colormode"));
+        }
+        if self.reachable.is_some() {
+            return Err(LightStateError::ReadOnly("reachable"));
+        }
+
+        let conflicts = [
+            ("bri", self.bri.is_some(), "bri_inc", self.bri_inc.is_some()),
+            ("sat", self.sat.is_some(), "sat_inc", self.sat_inc.is_some()),
+            ("hue", self.hue.is_some(), "hue_inc", self.hue_inc.is_some()),
+            ("ct", self.ct.is_some(), "ct_inc", self.ct_inc.is_some()),
+            ("xy", self.xy.is_some(), "xy_inc", self.xy_inc.is_some()),
+        ];
+        if let Some((a, _, b, _)) = conflicts.iter().find(|(_, a, _, b)| *a && *b) {
+            return Err(LightStateError::Conflicting(a, b));
+        }
+
+        check_range("bri", self.bri, 1..=254, "1 to 254")?;
+        check_range("sat", self.sat, 0..=MAX_SAT, "0 to 254")?;
+        check_range("ct", self.ct, 153..=500, "153 to 500")?;
+        check_range("bri_inc", self.bri_inc, -254..=254, "-254 to 254")?;
+        check_range("sat_inc", self.sat_inc, -254..=254, "-254 to 254")?;
+        check_range("hue_inc", self.hue_inc, -65534..=65534, "-65534 to 65534")?;
+        check_range("ct_inc", self.ct_inc, -65534..=65534, "-65534 to 65534")?;
+        if let Some((x, y)) = self.xy {
+            check_range("x", Some(x), 0.0..=1.0, "0 to 1")?;
+            check_range("y", Some(y), 0.0..=1.0, "0 to 1")?;
+        }
+        if let Some((x, y)) = self.xy_inc {
+            check_range("x_inc", Some(x), -0.5..=0.5, "-0.5 to 0.5")?;
+            check_range("y_inc", Some(y), -0.5..=0.5, "-0.5 to 0.5")?;
+        }
+
+        let changes_something = self.on.is_some()
+            || self.bri.is_some()
+            || self.hue.is_some()
+            || self.sat.is_some()
+            || self.xy.is_some()
+            || self.ct.is_some()
+            || self.effect.is_some()
+            || self.alert.is_some()
+            || self.bri_inc.is_some()
+            || self.sat_inc.is_some()
+            || self.hue_inc.is_some()
+            || self.ct_inc.is_some()
+            || self.xy_inc.is_some();
+        if !changes_something {
+            return Err(LightStateError::Empty);
+        }
+
+        Ok(())
+    }
+
     pub fn combine(base: &Self, diff: &Self) -> Self {
         let on = diff.on.or(base.on);
         let bri = diff.bri.or(base.bri);
@@ -192,47 +268,105 @@ impl LightState {
     }
 }
 
-impl Default for LightState {
-    fn default() -> Self {
-        Self {
-            on: None,
-            bri: None,
-            hue: None,
-            sat: None,
-            xy: None,
-            ct: None,
-            effect: None,
-            alert: None,
-            transitiontime: None,
-            bri_inc: None,
-            sat_inc: None,
-            hue_inc: None,
-            ct_inc: None,
-            xy_inc: None,
-            colormode: None,
-            reachable: None,
-        }
-    }
-}
-
+/// A light state given as query parameters. `xy` and `xy_inc` are split into `x`/`y` and `x_inc`/`y_inc`.
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct LightStateQuery {
+    on: Option<bool>,
     hue: Option<u16>,
     sat: Option<u8>,
     bri: Option<u8>,
+    x: Option<f32>,
+    y: Option<f32>,
+    ct: Option<u16>,
+    effect: Option<LightEffect>,
+    alert: Option<LightAlert>,
+    transitiontime: Option<u16>,
+    bri_inc: Option<i16>,
+    sat_inc: Option<i16>,
+    hue_inc: Option<i32>,
+    ct_inc: Option<i32>,
+    x_inc: Option<f32>,
+    y_inc: Option<f32>,
 }
 
-impl From<LightStateQuery> for LightState {
-    fn from(query: LightStateQuery) -> LightState {
-        LightState {
+impl TryFrom<LightStateQuery> for LightState {
+    type Error = LightStateError;
+
+    fn try_from(query: LightStateQuery) -> Result<LightState, LightStateError> {
+        let xy = match (query.x, query.y) {
+            (Some(x), Some(y)) => Some((x, y)),
+            (None, None) => None,
+            _ => return Err(LightStateError::Incomplete("x", "y")),
+        };
+        let xy_inc = match (query.x_inc, query.y_inc) {
+            (Some(x), Some(y)) => Some((x, y)),
+            (None, None) => None,
+            _ => return Err(LightStateError::Incomplete("x_inc", "y_inc")),
+        };
+
+        Ok(LightState {
+            on: query.on,
             hue: query.hue,
             sat: query.sat,
             bri: query.bri,
+            xy,
+            ct: query.ct,
+            effect: query.effect,
+            alert: query.alert,
+            transitiontime: query.transitiontime,
+            bri_inc: query.bri_inc,
+            sat_inc: query.sat_inc,
+            hue_inc: query.hue_inc,
+            ct_inc: query.ct_inc,
+            xy_inc,
             ..LightState::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LightStateError {
+    Empty,
+    OutOfRange {
+        attribute: &'static str,
+        value: String,
+        range: &'static str,
+    },
+    ReadOnly(&'static str),
+    Conflicting(&'static str, &'static str),
+    Incomplete(&'static str, &'static str),
+}
+
+impl Display for LightStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LightStateError::Empty => write!(f, "No attributes to change"),
+            LightStateError::OutOfRange { attribute, value, range } => {
+                write!(f, "{} must be in {}, got {}", attribute, range, value)
+            }
+            LightStateError::ReadOnly(attribute) => write!(f, "{} can't be set", attribute),
+            LightStateError::Conflicting(a, b) => write!(f, "{} and {} can't be set together", a, b),
+            LightStateError::Incomplete(a, b) => write!(f, "{} and {} must be given together", a, b),
         }
     }
 }
 
+impl std::error::Error for LightStateError {}
+
+fn check_range<T>(attribute: &'static str, value: Option<T>, range: RangeInclusive<T>, description: &'static str) -> Result<(), LightStateError>
+where
+    T: PartialOrd + Display,
+{
+    match value {
+        Some(value) if !range.contains(&value) => Err(LightStateError::OutOfRange {
+            attribute,
+            value: value.to_string(),
+            range: description,
+        }),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LightEffect {
@@ -248,7 +382,7 @@ pub enum LightAlert {
     Lselect,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LightColorMode {
     HS,
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub type RuleId = String;
+pub type RuleCollection = HashMap<RuleId, Rule>;
+
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_CONDITIONS: usize = 8;
+const MAX_ACTIONS: usize = 8;
+
+/// A bridge rule. Addresses are relative to the username, e.g. `/sensors/2/state/buttonevent`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<RuleStatus>,
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<Action>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lasttriggered: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestriggered: Option<u32>,
+}
+
+impl Rule {
+    /// The rule without the attributes the bridge maintains, suitable for keeping in a file
+    pub fn definition(&self) -> Rule {
+        Rule {
+            owner: None,
+            created: None,
+            lasttriggered: None,
+            timestriggered: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), RuleError> {
+        if self.name.is_empty() || self.name.len() > MAX_NAME_LENGTH {
+            return Err(RuleError::InvalidName(self.name.clone()));
+        }
+        if self.conditions.is_empty() || self.conditions.len() > MAX_CONDITIONS {
+            return Err(RuleError::ConditionCount(self.conditions.len()));
+        }
+        if self.actions.is_empty() || self.actions.len() > MAX_ACTIONS {
+            return Err(RuleError::ActionCount(self.actions.len()));
+        }
+
+        for condition in &self.conditions {
+            condition.validate()?;
+        }
+        for action in &self.actions {
+            action.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleStatus {
+    Enabled,
+    Disabled,
+    #[serde(rename = "resourcedeleted")]
+    ResourceDeleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub address: String,
+    pub operator: Operator,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl Condition {
+    fn validate(&self) -> Result<(), RuleError> {
+        let segments: Vec<&str> = self.address.trim_start_matches('/').split('/').collect();
+        let valid_address = match segments.as_slice() {
+            ["sensors", id, "state", _] | ["sensors", id, "config", _] => is_id(id),
+            ["lights", id, "state", "on"] | ["lights", id, "state", "reachable"] => is_id(id),
+            ["groups", id, "state", "any_on"] | ["groups", id, "state", "all_on"] => is_id(id),
+            ["config", "localtime"] => true,
+            _ => false,
+        };
+        if !self.address.starts_with('/') || !valid_address {
+            return Err(RuleError::InvalidConditionAddress(self.address.clone()));
+        }
+
+        let is_localtime = self.address == "/config/localtime";
+        let valid_operator = match (self.operator, &self.value) {
+            (Operator::Dx, None) | (Operator::Ddx, None) => !is_localtime,
+            (Operator::Eq, Some(_)) => !is_localtime,
+            (Operator::Gt, Some(value)) | (Operator::Lt, Some(value)) => {
+                !is_localtime && value.parse::<i64>().is_ok()
+            }
+            (Operator::Stable, Some(value)) | (Operator::NotStable, Some(value)) => {
+                !is_localtime && value.starts_with("PT")
+            }
+            (Operator::In, Some(value)) | (Operator::NotIn, Some(value)) => is_localtime && value.contains('/'),
+            _ => false,
+        };
+        if !valid_operator {
+            return Err(RuleError::InvalidOperator {
+                address: self.address.clone(),
+                operator: self.operator,
+                value: self.value.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operator {
+    #[serde(rename = "eq")]
+    Eq,
+    #[serde(rename = "gt")]
+    Gt,
+    #[serde(rename = "lt")]
+    Lt,
+    #[serde(rename = "dx")]
+    Dx,
+    #[serde(rename = "ddx")]
+    Ddx,
+    #[serde(rename = "stable")]
+    Stable,
+    #[serde(rename = "not stable")]
+    NotStable,
+    #[serde(rename = "in")]
+    In,
+    #[serde(rename = "not in")]
+    NotIn,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Action {
+    pub address: String,
+    pub method: ActionMethod,
+    pub body: Value,
+}
+
+impl Action {
+    fn validate(&self) -> Result<(), RuleError> {
+        let segments: Vec<&str> = self.address.trim_start_matches('/').split('/').collect();
+        let valid_address = match segments.as_slice() {
+            ["lights", id, "state"] | ["groups", id, "action"] => is_id(id),
+            ["sensors", id, "state"] | ["sensors", id, "config"] => is_id(id),
+            ["schedules", id] | ["rules", id] => is_id(id),
+            ["scenes", id] => !id.is_empty(),
+            ["scenes", id, "lightstates", light] => !id.is_empty() && is_id(light),
+            _ => false,
+        };
+        if !self.address.starts_with('/') || !valid_address {
+            return Err(RuleError::InvalidActionAddress(self.address.clone()));
+        }
+
+        if !self.body.is_object() {
+            return Err(RuleError::InvalidActionBody(self.address.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ActionMethod {
+    Put,
+    Post,
+    Delete,
+}
+
+fn is_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleError {
+    InvalidName(String),
+    ConditionCount(usize),
+    ActionCount(usize),
+    InvalidConditionAddress(String),
+    InvalidOperator {
+        address: String,
+        operator: Operator,
+        value: Option<String>,
+    },
+    InvalidActionAddress(String),
+    InvalidActionBody(String),
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RuleError::InvalidName(name) => {
+                write!(f, "Rule name must be 1 to {} characters: {:?}", MAX_NAME_LENGTH, name)
+            }
+            RuleError::ConditionCount(count) => {
+                write!(f, "Rules need 1 to {} conditions, found {}", MAX_CONDITIONS, count)
+            }
+            RuleError::ActionCount(count) => {
+                write!(f, "Rules need 1 to {} actions, found {}", MAX_ACTIONS, count)
+            }
+            RuleError::InvalidConditionAddress(address) => write!(f, "Invalid condition address: {}", address),
+            RuleError::InvalidOperator { address, operator, value } => {
+                write!(f, "Operator {:?} with value {:?} can't be used on {}", operator, value, address)
+            }
+            RuleError::InvalidActionAddress(address) => write!(f, "Invalid action address: {}", address),
+            RuleError::InvalidActionBody(address) => write!(f, "Action body for {} must be a JSON object", address),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::group::GroupNumber;
+use crate::light::{LightCollection, LightNumber, LightState};
+use crate::string_numbers::{light_numbers, optional_number};
+
+pub type SceneId = String;
+pub type SceneCollection = HashMap<SceneId, Scene>;
+
+/// A scene stored on the bridge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scene {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub scene_type: SceneType,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "optional_number")]
+    pub group: Option<GroupNumber>,
+    #[serde(with = "light_numbers")]
+    pub lights: Vec<LightNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub recycle: bool,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SceneType {
+    #[default]
+    LightScene,
+    GroupScene,
+}
+
+/// Attributes for creating a bridge scene. The bridge stores the current state of the scene's lights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneAttributes {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub scene_type: SceneType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "light_numbers")]
+    pub lights: Vec<LightNumber>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "optional_number")]
+    pub group: Option<GroupNumber>,
+    pub recycle: bool,
+}
+
+impl SceneAttributes {
+    pub fn for_lights(name: &str, lights: &[LightNumber]) -> Self {
+        Self {
+            name: name.to_string(),
+            scene_type: SceneType::LightScene,
+            lights: lights.to_vec(),
+            group: None,
+            recycle: false,
+        }
+    }
+
+    pub fn for_group(name: &str, group: GroupNumber) -> Self {
+        Self {
+            name: name.to_string(),
+            scene_type: SceneType::GroupScene,
+            lights: Vec::new(),
+            group: Some(group),
+            recycle: false,
+        }
+    }
+}
+
+/// A snapshot of light states kept outside the bridge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedScene {
+    pub name: String,
+    pub states: HashMap<LightNumber, LightState>,
+}
+
+impl SavedScene {
+    pub fn capture(name: &str, lights: &LightCollection) -> Self {
+        let states = lights
+            .iter()
+            .map(|(light_num, light)| (*light_num, light.state.snapshot()))
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            states,
+        }
+    }
+}
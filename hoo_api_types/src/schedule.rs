@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub type ScheduleId = String;
+pub type ScheduleCollection = HashMap<ScheduleId, Schedule>;
+
+/// A schedule stored on the bridge. `localtime` uses the bridge's time patterns,
+/// e.g. `W127/T07:00:00` for every day at 7am or `2020-06-01T07:00:00` for a single run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub command: ScheduleCommand,
+    pub localtime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ScheduleStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autodelete: Option<bool>,
+    #[serde(skip_serializing)]
+    pub created: Option<String>,
+}
+
+/// The request the bridge makes when a schedule fires. The address includes the username.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleCommand {
+    pub address: String,
+    pub method: String,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleStatus {
+    Enabled,
+    Disabled,
+}
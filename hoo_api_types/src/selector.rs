@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::group::GroupCollection;
+use crate::light::{LightCollection, LightNumber};
+
+/// Picks out lights by number, name or group. A comma separated list of terms, any of which can match:
+///
+/// - `3` or `5-7`: light numbers and ranges
+/// - `name:Kitchen*`: lights whose name matches a glob, ignoring case. `*` matches anything and `?` one character.
+/// - `group:Living*` or `group:2`: lights in groups matching a name glob or number
+/// - `active`: lights that are on and reachable
+/// - `all`: every light
+/// - any other word: an alias for another selector, see `expand_aliases`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightSelector {
+    terms: Vec<SelectorTerm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SelectorTerm {
+    All,
+    Active,
+    Range(LightNumber, LightNumber),
+    Name(String),
+    Group(String),
+    Alias(String),
+}
+
+impl LightSelector {
+    pub fn all() -> Self {
+        Self {
+            terms: vec![SelectorTerm::All],
+        }
+    }
+
+    pub fn light(light_num: LightNumber) -> Self {
+        Self {
+            terms: vec![SelectorTerm::Range(light_num, light_num)],
+        }
+    }
+
+    /// Replaces alias terms with the selectors they stand for. Names are matched ignoring case, and aliases
+    /// can't refer to other aliases.
+    pub fn expand_aliases(&self, aliases: &HashMap<String, LightSelector>) -> Result<Self, SelectorError> {
+        let mut terms = Vec::new();
+        for term in &self.terms {
+            match term {
+                SelectorTerm::Alias(name) => {
+                    let alias = aliases
+                        .iter()
+                        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+                        .map(|(_, selector)| selector)
+                        .ok_or_else(|| SelectorError::UnknownAlias(name.clone()))?;
+                    let nested = alias.terms.iter().find_map(|term| match term {
+                        SelectorTerm::Alias(nested) => Some(nested),
+                        _ => None,
+                    });
+                    if let Some(nested) = nested {
+                        return Err(SelectorError::NestedAlias(name.clone(), nested.clone()));
+                    }
+                    terms.extend(alias.terms.iter().cloned());
+                }
+                term => terms.push(term.clone()),
+            }
+        }
+
+        Ok(Self { terms })
+    }
+
+    /// Whether resolving needs the bridge's groups as well as its lights
+    pub fn needs_groups(&self) -> bool {
+        self.terms.iter().any(|term| matches!(term, SelectorTerm::Group(_)))
+    }
+
+    /// The numbers of the lights that match, sorted. Numbers that aren't in `lights` and aliases that haven't been
+    /// expanded never match.
+    pub fn resolve(&self, lights: &LightCollection, groups: &GroupCollection) -> Vec<LightNumber> {
+        let mut selected: Vec<LightNumber> = lights
+            .iter()
+            .filter(|(light_num, light)| {
+                self.terms.iter().any(|term| match term {
+                    SelectorTerm::All => true,
+                    SelectorTerm::Active => light.state.is_on() && light.state.is_reachable(),
+                    SelectorTerm::Range(first, last) => (first..=last).contains(light_num),
+                    SelectorTerm::Name(pattern) => glob_matches(pattern, &light.name),
+                    SelectorTerm::Group(pattern) => groups.iter().any(|(group_num, group)| {
+                        let group_matches = match pattern.parse::<u8>() {
+                            Ok(num) => num == *group_num,
+                            Err(_) => glob_matches(pattern, &group.name),
+                        };
+                        group_matches && group.lights.contains(light_num)
+                    }),
+                    SelectorTerm::Alias(_) => false,
+                })
+            })
+            .map(|(light_num, _)| *light_num)
+            .collect();
+
+        selected.sort_unstable();
+        selected
+    }
+}
+
+impl FromStr for LightSelector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s
+            .split(',')
+            .map(|term| parse_term(term.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { terms })
+    }
+}
+
+impl Display for LightSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|term| match term {
+                SelectorTerm::All => "all".to_string(),
+                SelectorTerm::Active => "active".to_string(),
+                SelectorTerm::Range(first, last) if first == last => first.to_string(),
+                SelectorTerm::Range(first, last) => format!("{}-{}", first, last),
+                SelectorTerm::Name(pattern) => format!("name:{}", pattern),
+                SelectorTerm::Group(pattern) => format!("group:{}", pattern),
+                SelectorTerm::Alias(name) => name.clone(),
+            })
+            .collect();
+
+        write!(f, "{}", terms.join(","))
+    }
+}
+
+fn parse_term(term: &str) -> Result<SelectorTerm, SelectorError> {
+    if let Some(pattern) = term.strip_prefix("name:") {
+        return non_empty(term, pattern).map(SelectorTerm::Name);
+    }
+    if let Some(pattern) = term.strip_prefix("group:") {
+        return non_empty(term, pattern).map(SelectorTerm::Group);
+    }
+
+    match term.to_lowercase().as_str() {
+        "" => Err(SelectorError::Empty),
+        "all" => Ok(SelectorTerm::All),
+        "active" => Ok(SelectorTerm::Active),
+        _ if is_alias(term) => Ok(SelectorTerm::Alias(term.to_string())),
+        _ => {
+            let (first, last) = match term.find('-') {
+                Some(dash) => (&term[..dash], &term[dash + 1..]),
+                None => (term, term),
+            };
+            match (first.trim().parse(), last.trim().parse()) {
+                (Ok(first), Ok(last)) if first <= last => Ok(SelectorTerm::Range(first, last)),
+                _ => Err(SelectorError::Invalid(term.to_string())),
+            }
+        }
+    }
+}
+
+/// Alias names start with a letter so they can't be confused with numbers and ranges
+fn is_alias(term: &str) -> bool {
+    term.starts_with(|c: char| c.is_alphabetic())
+        && term.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ')
+}
+
+fn non_empty(term: &str, pattern: &str) -> Result<String, SelectorError> {
+    if pattern.is_empty() {
+        Err(SelectorError::Invalid(term.to_string()))
+    } else {
+        Ok(pattern.to_string())
+    }
+}
+
+/// Case insensitive, with `*` for any run of characters and `?` for exactly one
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and how much of the text it has swallowed so far
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, swallowed)) => {
+                    p = star + 1;
+                    t = swallowed + 1;
+                    backtrack = Some((star, swallowed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorError {
+    Empty,
+    Invalid(String),
+    UnknownAlias(String),
+    NestedAlias(String, String),
+}
+
+impl Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SelectorError::Empty => write!(f, "Empty light selector"),
+            SelectorError::Invalid(term) => write!(
+                f,
+                "Invalid light selector {:?}. Expected a number, a range like 5-7, name:<glob>, group:<name>, active, all or an alias",
+                term
+            ),
+            SelectorError::UnknownAlias(name) => write!(f, "No light alias named {:?}", name),
+            SelectorError::NestedAlias(name, nested) => {
+                write!(f, "Light alias {:?} refers to another alias, {:?}", name, nested)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(selector: &str) -> Vec<SelectorTerm> {
+        selector.parse::<LightSelector>().unwrap().terms
+    }
+
+    #[test]
+    fn parses_terms() {
+        assert_eq!(
+            terms("3, 5-7,name:Kitchen*,group:2,ACTIVE,all,desk_lamps"),
+            vec![
+                SelectorTerm::Range(3, 3),
+                SelectorTerm::Range(5, 7),
+                SelectorTerm::Name("Kitchen*".to_string()),
+                SelectorTerm::Group("2".to_string()),
+                SelectorTerm::Active,
+                SelectorTerm::All,
+                SelectorTerm::Alias("desk_lamps".to_string()),
+            ]
+        );
+        assert_eq!(terms("group:living room"), vec![SelectorTerm::Group("living room".to_string())]);
+    }
+
+    #[test]
+    fn rejects_bad_terms() {
+        assert_eq!("".parse::<LightSelector>(), Err(SelectorError::Empty));
+        assert_eq!("1,".parse::<LightSelector>(), Err(SelectorError::Empty));
+        for selector in &["7-5", "1-", "256", "name:", "group:", "-3", "3!"] {
+            assert!(selector.parse::<LightSelector>().is_err(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn displays_the_way_it_parses() {
+        let selector: LightSelector = "3,5-7,name:k*,group:Living room,active,all,desk".parse().unwrap();
+        assert_eq!(selector.to_string(), "3,5-7,name:k*,group:Living room,active,all,desk");
+        assert_eq!(selector.to_string().parse::<LightSelector>().unwrap(), selector);
+    }
+
+    #[test]
+    fn globs_match() {
+        assert!(glob_matches("kitchen", "Kitchen"));
+        assert!(glob_matches("k*", "Kitchen"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*light*", "Hue lightstrip"));
+        assert!(glob_matches("lamp ?", "Lamp 2"));
+        assert!(glob_matches("*a*a*", "banana"));
+        assert!(glob_matches("desk**", "Desk"));
+
+        assert!(!glob_matches("kitchen", "Kitchen 2"));
+        assert!(!glob_matches("lamp ?", "Lamp 12"));
+        assert!(!glob_matches("?", ""));
+        assert!(!glob_matches("*z", "banana"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+pub type SensorNumber = u8;
+pub type SensorCollection = HashMap<SensorNumber, Sensor>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sensor {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub sensor_type: SensorType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modelid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturername: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uniqueid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swversion: Option<String>,
+    #[serde(default)]
+    pub state: SensorState,
+    #[serde(default)]
+    pub config: SensorConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SensorType {
+    ZLLPresence,
+    ZLLLightLevel,
+    ZLLTemperature,
+    ZLLSwitch,
+    ZGPSwitch,
+    Daylight,
+    CLIPGenericStatus,
+    CLIPGenericFlag,
+    CLIPPresence,
+    CLIPTemperature,
+    CLIPLightLevel,
+    CLIPSwitch,
+    #[serde(other)]
+    Other,
+}
+
+/// Readings reported by a sensor. Which attributes are present depends on the sensor type.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensorState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lightlevel: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dark: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daylight: Option<bool>,
+    /// Hundredths of a degree Celsius
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buttonevent: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lastupdated: Option<String>,
+}
+
+impl SensorState {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn status(mut self, status: i32) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn flag(mut self, flag: bool) -> Self {
+        self.flag = Some(flag);
+        self
+    }
+
+    pub fn temperature_celsius(&self) -> Option<f64> {
+        self.temperature.map(|t| f64::from(t) / 100.0)
+    }
+
+    /// Converts the bridge's logarithmic light level to lux
+    pub fn lux(&self) -> Option<f64> {
+        self.lightlevel.map(|level| 10_f64.powf((f64::from(level) - 1.0) / 10_000.0))
+    }
+}
+
+/// Sensor settings. Which attributes are accepted depends on the sensor type.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensorConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reachable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivitymax: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tholddark: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tholdoffset: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunriseoffset: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunsetoffset: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configured: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledindication: Option<bool>,
+}
+
+impl SensorConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn on(mut self, is_on: bool) -> Self {
+        self.on = Some(is_on);
+        self
+    }
+
+    pub fn sensitivity(mut self, sensitivity: u8) -> Self {
+        self.sensitivity = Some(sensitivity);
+        self
+    }
+
+    pub fn tholddark(mut self, threshold: u16) -> Self {
+        self.tholddark = Some(threshold);
+        self
+    }
+
+    pub fn tholdoffset(mut self, offset: u16) -> Self {
+        self.tholdoffset = Some(offset);
+        self
+    }
+
+    pub fn sunriseoffset(mut self, minutes: i8) -> Self {
+        self.sunriseoffset = Some(minutes);
+        self
+    }
+
+    pub fn sunsetoffset(mut self, minutes: i8) -> Self {
+        self.sunsetoffset = Some(minutes);
+        self
+    }
+
+    pub fn ledindication(mut self, enabled: bool) -> Self {
+        self.ledindication = Some(enabled);
+        self
+    }
+}
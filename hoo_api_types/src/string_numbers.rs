@@ -0,0 +1,56 @@
+//! The bridge sends light and group ids as strings even though they are always numbers
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub mod light_numbers {
+    use super::*;
+
+    pub fn serialize<S, T>(numbers: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Display,
+    {
+        serializer.collect_seq(numbers.iter().map(|number| number.to_string()))
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|number| number.parse().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+pub mod optional_number {
+    use super::*;
+
+    pub fn serialize<S, T>(number: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Display,
+    {
+        match number {
+            Some(number) => serializer.serialize_some(&number.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|number| number.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
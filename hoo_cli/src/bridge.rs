@@ -0,0 +1,20 @@
+use anyhow::{anyhow, Result};
+
+use hoo_api::KnownBridges;
+
+/// Forgets the certificate pinned for a bridge, so the next one it presents is trusted. Takes the host the way it
+/// appears in the base URI, e.g. `192.168.1.20`, or the whole base URI.
+pub fn forget(host: &str) -> Result<()> {
+    let known_bridges = KnownBridges::default_path()
+        .map(KnownBridges::new)
+        .ok_or_else(|| anyhow!("There's no config directory to keep known bridges in"))?;
+
+    let host = host.split("://").last().unwrap_or(host);
+    let host = host.split('/').next().unwrap_or(host);
+    if known_bridges.remove(host)? {
+        println!("Forgot the certificate for {}. The next one it presents will be trusted", host);
+        Ok(())
+    } else {
+        Err(anyhow!("No certificate is pinned for {} in {}", host, known_bridges.path().display()))
+    }
+}
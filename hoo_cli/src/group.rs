@@ -0,0 +1,66 @@
+use anyhow::Result;
+
+use hoo_api::{Color, GroupAction, GroupAttributes, LightBackend, LightState, Profile};
+
+use crate::options::GroupCommand;
+use crate::{report, select_numbers};
+
+pub async fn run<B: LightBackend>(connection: &B, profile: &Profile, command: GroupCommand) -> Result<()> {
+    use GroupCommand::*;
+    match command {
+        List { group_num } => {
+            if let Some(group_num) = group_num {
+                let group = connection.get_group(group_num).await?;
+                dbg!(group);
+            } else {
+                let groups = connection.get_all_groups().await?;
+                dbg!(groups);
+            }
+        },
+        Create { name, lights, group_type, class } => {
+            let lights = match lights {
+                Some(selector) => select_numbers(connection, profile, &selector).await?,
+                None => Vec::new(),
+            };
+            let mut attributes = GroupAttributes::new()
+                .name(&name)
+                .lights(&lights)
+                .group_type(group_type);
+            attributes.class = class;
+            let group_num = connection.create_group(&attributes).await?;
+            println!("Created group {}", group_num);
+        },
+        Update { group_num, name, lights, class } => {
+            let lights = match lights {
+                Some(selector) => select_numbers(connection, profile, &selector).await?,
+                None => Vec::new(),
+            };
+            let attributes = GroupAttributes {
+                name,
+                lights,
+                group_type: None,
+                class,
+            };
+            report(connection.update_group(group_num, &attributes).await?);
+        },
+        Delete { group_num } => { report(connection.delete_group(group_num).await?); },
+        On { group_num } => { set_state(connection, profile, group_num, LightState::new().on(true)).await?; },
+        Off { group_num } => { set_state(connection, profile, group_num, LightState::new().on(false)).await?; },
+        Hue { group_num, value } => { set_state(connection, profile, group_num, LightState::new().hue(value)).await?; },
+        Sat { group_num, value } => { set_state(connection, profile, group_num, LightState::new().sat(value)).await?; },
+        Bri { group_num, value } => { set_state(connection, profile, group_num, LightState::new().bri(value)).await?; },
+        Hsb { group_num, hue, sat, bri } => {
+            let new_state = LightState::new().color(&Color::from_hsv(hue, sat, bri));
+            set_state(connection, profile, group_num, new_state).await?;
+        },
+    };
+
+    Ok(())
+}
+
+async fn set_state<B: LightBackend>(connection: &B, profile: &Profile, group_num: u8, mut state: LightState) -> Result<()> {
+    state.transitiontime = state.transitiontime.or(profile.transition_time);
+    let action = GroupAction::from(state);
+    report(connection.set_group_action(group_num, &action).await?);
+    Ok(())
+}
@@ -1,75 +1,229 @@
+use std::time::Duration;
+
 use structopt::StructOpt;
-use hoo_api::{HueClient, Color, LightState};
+use tokio::sync::broadcast::RecvError;
+use hoo_api::{
+    BridgeTrust, Config, HueClient, Color, Light, LightBackend, LightCollection, LightColorMode, MAX_SAT,
+    LightEvent, LightNumber, LightSelector, LightState, Profile, SceneStore, Sensor, SensorType, StateChangeResult,
+};
 
+mod bridge;
+mod group;
 mod options;
+mod rule;
+mod scene;
+mod setup;
+mod token;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    dotenv::dotenv()?;
+    dotenv::dotenv().ok();
     let options = options::Options::from_args();
-    
-    let connection = HueClient::new(&options.hue_base_uri, &options.hue_user_id);
 
     use options::Command::*;
+    if let Setup(setup) = options.command {
+        return setup::setup(setup, options.config.as_deref(), options.profile.as_deref()).await;
+    }
+    if let Bridge(options::BridgeCommand::Forget { host }) = options.command {
+        return bridge::forget(&host);
+    }
+    if let Token { name, control, lights } = options.command {
+        token::token(&name, control, lights.as_ref());
+        return Ok(());
+    }
+
+    let config = Config::load(options.config.as_deref())?;
+    let profile = config.profile(options.profile.as_deref())?;
+
+    let env = options.hue_base_uri.zip(options.hue_user_id);
+    let bridge = profile
+        .credentials(env, options.profile.is_some())
+        .map_err(|e| anyhow::anyhow!("{}. Run `hoo setup` to create them", e))?;
+    
+    let policy = profile.request_policy(options.bridge_timeout, options.bridge_retries);
+    let connection = HueClient::with_trust(&bridge.base_uri, &bridge.user_id, BridgeTrust::for_bridge(&bridge))
+        .with_policy(policy);
+
     match options.command {
-        On { light_num } => { connection.on(light_num).await?; },
-        Off { light_num } => { connection.off(light_num).await?; },
-        Toggle { light_num } => {
-            let light = connection.get_light(light_num).await?;
-            let new_state = match light.state.on {
-                Some(is_on) => LightState::new().on(!is_on),
-                None => LightState::new().on(true),
-            };
-            connection.set_state(light_num, &new_state).await?;
+        On { lights } => set_each(&connection, &profile, &lights, |_| LightState::new().on(true)).await?,
+        Off { lights } => set_each(&connection, &profile, &lights, |_| LightState::new().on(false)).await?,
+        Toggle { lights } => set_each(&connection, &profile, &lights, |light| LightState::new().on(!light.state.is_on())).await?,
+        TransitionTime { lights, value } => set_each(&connection, &profile, &lights, |_| LightState::new().transitiontime(value)).await?,
+        Red { lights, value } => {
+            set_each(&connection, &profile, &lights, |light| {
+                let (_, g, b) = light.color().unwrap_or_default().rgb();
+                LightState::new()
+                    .color(&Color::from_rgb(value, g, b))
+                    .sat(MAX_SAT)
+            }).await?;
         },
-        TransitionTime { light_num, value } => { connection.transition_time(light_num, value).await?; },
-        Red { light_num, value } => {
-            let (_, g, b) = connection.get_light(light_num).await?.color().unwrap_or_default().rgb();
-            let new_state = LightState::new()
-                .color(&Color::from_rgb(value, g, b))
-                .sat(255);
-            connection.set_state(light_num, &new_state).await?;
+        Green { lights, value } => {
+            set_each(&connection, &profile, &lights, |light| {
+                let (r, _, b) = light.color().unwrap_or_default().rgb();
+                LightState::new()
+                    .color(&Color::from_rgb(r, value, b))
+                    .sat(MAX_SAT)
+            }).await?;
         },
-        Green { light_num, value } => {
-            let (r, _, b) = connection.get_light(light_num).await?.color().unwrap_or_default().rgb();
-            let new_state = LightState::new()
-                .color(&Color::from_rgb(r, value, b))
-                .sat(255);
-            connection.set_state(light_num, &new_state).await?;
+        Blue { lights, value } => {
+            set_each(&connection, &profile, &lights, |light| {
+                let (r, g, _) = light.color().unwrap_or_default().rgb();
+                LightState::new()
+                    .color(&Color::from_rgb(r, g, value))
+                    .sat(MAX_SAT)
+            }).await?;
         },
-        Blue { light_num, value } => {
-            let (r, g, _) = connection.get_light(light_num).await?.color().unwrap_or_default().rgb();
-            let new_state = LightState::new()
-                .color(&Color::from_rgb(r, g, value))
-                .sat(255);
-            connection.set_state(light_num, &new_state).await?;
-        },
-        RGB { light_num, red, green, blue } => {
+        Rgb { lights, red, green, blue } => {
             let new_state = LightState::new()
                 .color(&Color::from_rgb(red, green, blue))
-                .sat(255);
-            connection.set_state(light_num, &new_state).await?;
+                .sat(MAX_SAT);
+            set_each(&connection, &profile, &lights, |_| new_state.clone()).await?;
         },
-        Hue { light_num, value } => { connection.set_state(light_num, &LightState::new().hue(value)).await?; },
-        Sat { light_num, value } => { connection.set_state(light_num, &LightState::new().sat(value)).await?; },
-        Bri { light_num, value } => { connection.set_state(light_num, &LightState::new().bri(value)).await?; },
-        HSB { light_num, hue, sat, bri } => {
+        Hue { lights, value } => set_each(&connection, &profile, &lights, |_| LightState::new().hue(value)).await?,
+        Sat { lights, value } => set_each(&connection, &profile, &lights, |_| LightState::new().sat(value)).await?,
+        Bri { lights, value } => set_each(&connection, &profile, &lights, |_| LightState::new().bri(value)).await?,
+        Hsb { lights, hue, sat, bri } => {
             let new_state = LightState::new().color(&Color::from_hsv(hue, sat, bri));
-            connection.set_state(light_num, &new_state).await?;
+            set_each(&connection, &profile, &lights, |_| new_state.clone()).await?;
+        },
+        List { active, lights } => {
+            let selector = lights.unwrap_or_else(LightSelector::all);
+            let mut lights = select(&connection, &profile, &selector).await?;
+            if active {
+                lights.retain(|_, light| light.state.is_on() && light.state.is_reachable());
+            }
+            dbg!(lights);
         },
-        List { active, light_num } => {
-            if let Some(light_num) = light_num {
-                let light = connection.get_light(light_num).await?;
-                dbg!(light);
-            } else if active {
-                let lights = connection.get_active_lights().await?;
-                dbg!(lights);
+        Sensors { sensor_num } => {
+            if let Some(sensor_num) = sensor_num {
+                let sensor = connection.get_sensor(sensor_num).await?;
+                dbg!(sensor);
             } else {
-                let lights = connection.get_all_lights().await?;
-                dbg!(lights);
+                let mut sensors: Vec<_> = connection.get_sensors().await?.into_iter().collect();
+                sensors.sort_by_key(|(sensor_num, _)| *sensor_num);
+                for (sensor_num, sensor) in sensors {
+                    println!("{:>3}  {}", sensor_num, describe_sensor(&sensor));
+                }
+            }
+        },
+        Watch { interval } => {
+            let watcher = connection.watch_lights(Duration::from_millis(interval.get()));
+            let mut events = watcher.subscribe();
+            loop {
+                match events.recv().await {
+                    Ok(event) => println!("{}", describe_event(&event)),
+                    Err(RecvError::Lagged(skipped)) => eprintln!("Missed {} changes", skipped),
+                    Err(RecvError::Closed) => break,
+                }
             }
         },
+        Group(command) => { group::run(&connection, &profile, command).await?; },
+        Rule(command) => { rule::run(&connection, command).await?; },
+        Scene(command) => {
+            let store = SceneStore::new(&options.scene_file);
+            scene::run(&connection, &profile, &store, command).await?;
+        },
+        Bridge(_) | Setup(_) | Token { .. } => unreachable!(),
     };
 
     Ok(())
 }
+
+/// The lights a selector matches, or an error if there aren't any
+async fn select<B: LightBackend>(connection: &B, profile: &Profile, selector: &LightSelector) -> anyhow::Result<LightCollection> {
+    let lights = connection.select_lights(&selector.expand_aliases(&profile.aliases)?).await?;
+    if lights.is_empty() {
+        anyhow::bail!("No lights match {}", selector);
+    }
+    Ok(lights)
+}
+
+/// The sorted numbers of the lights a selector matches
+async fn select_numbers<B: LightBackend>(connection: &B, profile: &Profile, selector: &LightSelector) -> anyhow::Result<Vec<LightNumber>> {
+    let mut light_nums: Vec<_> = select(connection, profile, selector).await?.into_keys().collect();
+    light_nums.sort_unstable();
+    Ok(light_nums)
+}
+
+/// Sets every selected light to a state worked out from its current one, using the profile's transition time
+/// if the state doesn't have one. Keeps going if some lights fail.
+async fn set_each<B, F>(connection: &B, profile: &Profile, selector: &LightSelector, state_for: F) -> anyhow::Result<()>
+where
+    B: LightBackend,
+    F: Fn(&Light) -> LightState,
+{
+    let mut states: Vec<_> = select(connection, profile, selector)
+        .await?
+        .iter()
+        .map(|(light_num, light)| {
+            let mut state = state_for(light);
+            state.transitiontime = state.transitiontime.or(profile.transition_time);
+            (*light_num, state)
+        })
+        .collect();
+    states.sort_unstable_by_key(|(light_num, _)| *light_num);
+
+    let mut failures = 0;
+    for (light_num, result) in connection.set_states(&states).await {
+        match result {
+            Ok(result) => report(result),
+            Err(e) => {
+                eprintln!("Light {}: {}", light_num, e);
+                failures += 1;
+            },
+        }
+    }
+    if failures > 0 {
+        anyhow::bail!("{} of {} lights couldn't be set", failures, states.len());
+    }
+    Ok(())
+}
+
+fn report(result: StateChangeResult) {
+    for error in result.failed {
+        eprintln!("{}", error);
+    }
+}
+
+fn describe_event(event: &LightEvent) -> String {
+    let change = match event {
+        LightEvent::Added { name, .. } => format!("added ({})", name),
+        LightEvent::Removed { .. } => "removed".to_string(),
+        LightEvent::Power { on, .. } => if *on { "on".to_string() } else { "off".to_string() },
+        LightEvent::Brightness { bri, .. } => format!("brightness {}", bri),
+        LightEvent::Color { colormode, hue, sat, xy, ct, .. } => match (colormode, xy, ct) {
+            (Some(LightColorMode::XY), Some((x, y)), _) => format!("color xy {:.4}, {:.4}", x, y),
+            (Some(LightColorMode::CT), _, Some(ct)) => format!("color temperature {}", ct),
+            _ => format!("color hue {}, sat {}", hue.unwrap_or_default(), sat.unwrap_or_default()),
+        },
+        LightEvent::Reachability { reachable, .. } => if *reachable { "reachable".to_string() } else { "unreachable".to_string() },
+    };
+
+    format!("{:>3}  {}", event.light(), change)
+}
+
+fn describe_sensor(sensor: &Sensor) -> String {
+    let state = &sensor.state;
+    let reading = match sensor.sensor_type {
+        SensorType::ZLLPresence | SensorType::CLIPPresence => state.presence.map(|p| format!("presence: {}", p)),
+        SensorType::ZLLLightLevel | SensorType::CLIPLightLevel => state.lux().map(|lux| format!("{:.0} lux", lux)),
+        SensorType::ZLLTemperature | SensorType::CLIPTemperature => state.temperature_celsius().map(|t| format!("{:.1}°C", t)),
+        SensorType::ZLLSwitch | SensorType::ZGPSwitch | SensorType::CLIPSwitch => state.buttonevent.map(|e| format!("last button event: {}", e)),
+        SensorType::Daylight => state.daylight.map(|d| format!("daylight: {}", d)),
+        SensorType::CLIPGenericStatus => state.status.map(|s| format!("status: {}", s)),
+        SensorType::CLIPGenericFlag => state.flag.map(|f| format!("flag: {}", f)),
+        SensorType::Other => None,
+    };
+
+    let mut description = format!("{} ({:?})", sensor.name, sensor.sensor_type);
+    if let Some(reading) = reading {
+        description += &format!(" - {}", reading);
+    }
+    if let Some(battery) = sensor.config.battery {
+        description += &format!(", battery {}%", battery);
+    }
+    if sensor.config.reachable == Some(false) {
+        description += ", unreachable";
+    }
+    description
+}
@@ -1,32 +1,204 @@
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
 use structopt::StructOpt;
 
+use hoo_api::{GroupType, LightSelector};
+
 #[derive(StructOpt, Debug)]
 pub struct Options {
-    #[structopt(env, hide_env_values = true)]
-    pub hue_base_uri: String,
-    #[structopt(env, hide_env_values = true)]
-    pub hue_user_id: String,
+    #[structopt(long, env, hide_env_values = true)]
+    pub hue_base_uri: Option<String>,
+    #[structopt(long, env, hide_env_values = true)]
+    pub hue_user_id: Option<String>,
+    /// Where saved scenes are kept
+    #[structopt(long, env = "HOO_SCENE_FILE", default_value = "scenes.json")]
+    pub scene_file: PathBuf,
+    /// Config file to read instead of hoo/config.toml in the user config directory
+    #[structopt(long, env = "HOO_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Config profile to use instead of the default one. Its bridge takes precedence over HUE_BASE_URI and HUE_USER_ID.
+    #[structopt(long, env = "HOO_PROFILE")]
+    pub profile: Option<String>,
+    /// Milliseconds to wait for the bridge to answer. Defaults to the profile's bridge_timeout, or 5000.
+    #[structopt(long, env = "HOO_BRIDGE_TIMEOUT")]
+    pub bridge_timeout: Option<u64>,
+    /// Times to retry reads the bridge doesn't answer. Defaults to the profile's bridge_retries, or 2.
+    #[structopt(long, env = "HOO_BRIDGE_RETRIES")]
+    pub bridge_retries: Option<u32>,
     #[structopt(subcommand)]
     pub command: Command,
 }
 
+/// Commands that take `lights` accept a selector: numbers and ranges like `3,5-7`, `name:Kitchen*`,
+/// `group:Living room`, `active`, `all` or an alias from the config file
 #[derive(StructOpt, Debug)]
 pub enum Command {
-    On { light_num: u8 },
-    Off { light_num: u8 },
-    Toggle { light_num: u8 },
-    TransitionTime { light_num: u8, value: u16 },
-    Red { light_num: u8, value: f64 },
-    Green { light_num: u8, value: f64 },
-    Blue { light_num: u8, value: f64 },
-    RGB { light_num: u8, red: f64, green: f64, blue: f64},
-    Hue { light_num: u8, value: u16 },
-    Sat { light_num: u8, value: u8 },
-    Bri { light_num: u8, value: u8 },
-    HSB { light_num: u8, hue: u16, sat: u8, bri: u8 },
+    On { lights: LightSelector },
+    Off { lights: LightSelector },
+    Toggle { lights: LightSelector },
+    TransitionTime { lights: LightSelector, value: u16 },
+    Red { lights: LightSelector, value: f64 },
+    Green { lights: LightSelector, value: f64 },
+    Blue { lights: LightSelector, value: f64 },
+    #[structopt(name = "rgb")]
+    Rgb { lights: LightSelector, red: f64, green: f64, blue: f64},
+    Hue { lights: LightSelector, value: u16 },
+    Sat { lights: LightSelector, value: u8 },
+    Bri { lights: LightSelector, value: u8 },
+    #[structopt(name = "hsb")]
+    Hsb { lights: LightSelector, hue: u16, sat: u8, bri: u8 },
     List {
-        light_num: Option<u8>,
+        lights: Option<LightSelector>,
         #[structopt(long)]
         active: bool,     
     },
+    /// Print light changes as they happen, including ones made by switches and other apps
+    Watch {
+        /// Milliseconds between polls of the bridge
+        #[structopt(long, default_value = "1000")]
+        interval: NonZeroU64,
+    },
+    /// Control rooms, zones and other groups of lights
+    Group(GroupCommand),
+    /// List motion, light level, temperature, switch and CLIP sensors
+    Sensors { sensor_num: Option<u8> },
+    /// Manage bridge rules kept as JSON files
+    Rule(RuleCommand),
+    /// Save and apply scenes, either locally or on the bridge
+    Scene(SceneCommand),
+    /// Manage the certificates trusted for bridges reached over HTTPS
+    Bridge(BridgeCommand),
+    /// Find a bridge, register a new user with it and add it to the config file as a profile named by --profile, or
+    /// "default"
+    Setup(SetupOptions),
+    /// Make a new server API token and print the config file entry for it
+    Token {
+        name: String,
+        /// Let the token change lights, not just read them
+        #[structopt(long)]
+        control: bool,
+        /// Only let the token control these lights
+        #[structopt(long, requires = "control")]
+        lights: Option<LightSelector>,
+    },
 }
+
+
+#[derive(StructOpt, Debug)]
+pub struct SetupOptions {
+    /// Skip discovery and use this bridge
+    #[structopt(long)]
+    pub bridge: Option<String>,
+    /// Extra addresses to probe if SSDP and mDNS find nothing
+    #[structopt(long)]
+    pub candidate: Vec<String>,
+    /// Talk to the bridge over HTTPS, trusting the certificate it presents now from then on
+    #[structopt(long)]
+    pub https: bool,
+    #[structopt(long, default_value = "hoo#cli")]
+    pub devicetype: String,
+    /// Seconds to wait for the link button to be pressed
+    #[structopt(long, default_value = "30")]
+    pub timeout: u64,
+    /// Write HUE_BASE_URI and HUE_USER_ID to this env file instead of adding a profile to the config file
+    #[structopt(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum BridgeCommand {
+    /// Stop trusting the certificate pinned for a bridge, e.g. after it was reset or replaced
+    Forget {
+        /// The bridge's host as it appears in the base URI, or the whole base URI
+        host: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum GroupCommand {
+    List { group_num: Option<u8> },
+    Create {
+        name: String,
+        lights: Option<LightSelector>,
+        /// LightGroup, Room, Zone or Entertainment
+        #[structopt(long = "type", default_value = "LightGroup")]
+        group_type: GroupType,
+        /// Room class, e.g. "Living room"
+        #[structopt(long)]
+        class: Option<String>,
+    },
+    Update {
+        group_num: u8,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        lights: Option<LightSelector>,
+        #[structopt(long)]
+        class: Option<String>,
+    },
+    Delete { group_num: u8 },
+    On { group_num: u8 },
+    Off { group_num: u8 },
+    Hue { group_num: u8, value: u16 },
+    Sat { group_num: u8, value: u8 },
+    Bri { group_num: u8, value: u8 },
+    #[structopt(name = "hsb")]
+    Hsb { group_num: u8, hue: u16, sat: u8, bri: u8 },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum SceneCommand {
+    List {
+        /// List the scenes stored on the bridge instead of saved scenes
+        #[structopt(long)]
+        bridge: bool,
+    },
+    /// Save the current state of the given lights, or of every light if none are given
+    Save {
+        name: String,
+        lights: Option<LightSelector>,
+        #[structopt(long)]
+        bridge: bool,
+        /// Store a bridge scene for this group instead of a list of lights
+        #[structopt(long, requires = "bridge")]
+        group: Option<u8>,
+    },
+    Apply {
+        /// Scene name, or id for bridge scenes
+        name: String,
+        #[structopt(long)]
+        bridge: bool,
+        #[structopt(long)]
+        transition_time: Option<u16>,
+        /// Group to recall a bridge scene in. Defaults to all lights
+        #[structopt(long, requires = "bridge")]
+        group: Option<u8>,
+    },
+    Delete {
+        name: String,
+        #[structopt(long)]
+        bridge: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum RuleCommand {
+    List,
+    Show { rule_id: String },
+    /// Check a rule file without sending it to the bridge
+    Validate { file: PathBuf },
+    /// Create a rule from a file, or update the bridge rule with the same name or the given id
+    Push {
+        file: PathBuf,
+        #[structopt(long)]
+        id: Option<String>,
+    },
+    /// Write a bridge rule to a file, or print it if no file is given
+    Pull {
+        rule_id: String,
+        #[structopt(long)]
+        output: Option<PathBuf>,
+    },
+    Delete { rule_id: String },
+}
\ No newline at end of file
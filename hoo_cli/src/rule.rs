@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use hoo_api::{HueClient, Rule};
+
+use crate::options::RuleCommand;
+use crate::report;
+
+/// Rules only exist on the bridge, so unlike the light commands these need a `HueClient`
+pub async fn run(connection: &HueClient, command: RuleCommand) -> Result<()> {
+    use RuleCommand::*;
+    match command {
+        List => {
+            let mut rules: Vec<_> = connection.get_all_rules().await?.into_iter().collect();
+            rules.sort_by_key(|(rule_id, _)| rule_id.parse::<u32>().unwrap_or_default());
+            for (rule_id, rule) in rules {
+                println!("{:>3}  {}", rule_id, rule.name);
+            }
+        },
+        Show { rule_id } => {
+            let rule = connection.get_rule(&rule_id).await?;
+            println!("{}", serde_json::to_string_pretty(&rule)?);
+        },
+        Validate { file } => {
+            read_rule(&file)?.validate()?;
+            println!("{} is valid", file.display());
+        },
+        Push { file, id } => {
+            let rule = read_rule(&file)?;
+            rule.validate()?;
+
+            let existing_id = match id {
+                Some(id) => Some(id),
+                None => connection
+                    .get_all_rules()
+                    .await?
+                    .into_iter()
+                    .find(|(_, existing)| existing.name == rule.name)
+                    .map(|(rule_id, _)| rule_id),
+            };
+
+            match existing_id {
+                Some(rule_id) => {
+                    report(connection.update_rule(&rule_id, &rule).await?);
+                    println!("Updated rule {}", rule_id);
+                },
+                None => {
+                    let rule_id = connection.create_rule(&rule).await?;
+                    println!("Created rule {}", rule_id);
+                },
+            }
+        },
+        Pull { rule_id, output } => {
+            let rule = connection.get_rule(&rule_id).await?.definition();
+            let contents = serde_json::to_string_pretty(&rule)?;
+            match output {
+                Some(path) => std::fs::write(path, contents + "\n")?,
+                None => println!("{}", contents),
+            }
+        },
+        Delete { rule_id } => { report(connection.delete_rule(&rule_id).await?); },
+    };
+
+    Ok(())
+}
+
+fn read_rule(path: &Path) -> Result<Rule> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
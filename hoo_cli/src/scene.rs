@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Result};
+
+use hoo_api::{HueClient, LightBackend, LightSelector, Profile, Scene, SceneAttributes, SceneId, SceneStore};
+
+use crate::options::SceneCommand;
+use crate::{report, select_numbers};
+
+/// Bridge scenes use endpoints only `HueClient` has. Locally saved scenes go through `save_local` and
+/// `apply_local`, which work with any `LightBackend`.
+pub async fn run(connection: &HueClient, profile: &Profile, store: &SceneStore, command: SceneCommand) -> Result<()> {
+    use SceneCommand::*;
+    match command {
+        List { bridge: true } => {
+            let scenes = connection.get_all_scenes().await?;
+            dbg!(scenes);
+        },
+        List { bridge: false } => {
+            let mut names: Vec<_> = store.load()?.into_keys().collect();
+            names.sort();
+            for name in names {
+                println!("{}", name);
+            }
+        },
+        Save { name, lights, bridge: true, group } => {
+            let attributes = match group {
+                Some(group) => SceneAttributes::for_group(&name, group),
+                None => {
+                    let selector = lights.unwrap_or_else(LightSelector::all);
+                    SceneAttributes::for_lights(&name, &select_numbers(connection, profile, &selector).await?)
+                },
+            };
+            let scene_id = connection.create_scene(&attributes).await?;
+            println!("Created scene {}", scene_id);
+        },
+        Save { name, lights, bridge: false, .. } => save_local(connection, profile, store, &name, lights).await?,
+        Apply { name, bridge: true, group, .. } => {
+            let (scene_id, scene) = find_bridge_scene(connection, &name).await?;
+            report(connection.recall_scene(&scene_id, group.or(scene.group)).await?);
+        },
+        Apply { name, bridge: false, transition_time, .. } => apply_local(connection, store, &name, transition_time).await?,
+        Delete { name, bridge: true } => {
+            let (scene_id, _) = find_bridge_scene(connection, &name).await?;
+            report(connection.delete_scene(&scene_id).await?);
+        },
+        Delete { name, bridge: false } => {
+            if !store.remove(&name)? {
+                return Err(anyhow!("No saved scene named {}", name));
+            }
+        },
+    };
+
+    Ok(())
+}
+
+async fn save_local<B: LightBackend>(
+    connection: &B,
+    profile: &Profile,
+    store: &SceneStore,
+    name: &str,
+    lights: Option<LightSelector>,
+) -> Result<()> {
+    let lights = match lights {
+        Some(selector) => select_numbers(connection, profile, &selector).await?,
+        None => Vec::new(),
+    };
+    let scene = connection.capture_scene(name, &lights).await?;
+    store.save(scene)?;
+    println!("Saved scene {} to {}", name, store.path().display());
+    Ok(())
+}
+
+async fn apply_local<B: LightBackend>(connection: &B, store: &SceneStore, name: &str, transition_time: Option<u16>) -> Result<()> {
+    let scene = store.get(name)?.ok_or_else(|| anyhow!("No saved scene named {}", name))?;
+    report(connection.apply_scene(&scene, transition_time).await?);
+    Ok(())
+}
+
+// Bridge scene names aren't unique, so an exact id match wins over a name match
+async fn find_bridge_scene(connection: &HueClient, name_or_id: &str) -> Result<(SceneId, Scene)> {
+    let mut scenes = connection.get_all_scenes().await?;
+    if let Some(scene) = scenes.remove(name_or_id) {
+        return Ok((name_or_id.to_string(), scene));
+    }
+
+    scenes
+        .into_iter()
+        .find(|(_, scene)| scene.name == name_or_id)
+        .ok_or_else(|| anyhow!("No bridge scene named {}", name_or_id))
+}
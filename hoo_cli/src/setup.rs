@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use toml::value::{Table, Value};
+
+use hoo_api::discovery::DEFAULT_FALLBACK_URIS;
+use hoo_api::{BridgeTrust, Config, DiscoveredBridge, KnownBridges};
+
+use crate::options::SetupOptions;
+
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_PROFILE: &str = "default";
+
+/// Where the new credentials go
+enum Destination {
+    Config(PathBuf),
+    EnvFile(PathBuf),
+}
+
+/// Registers with a bridge and saves the credentials as a bridge and a profile called `profile` in the config file,
+/// or in an env file if `--output` is given
+pub async fn setup(options: SetupOptions, config: Option<&Path>, profile: Option<&str>) -> Result<()> {
+    let profile = profile.unwrap_or(DEFAULT_PROFILE);
+    // Checked first, so the link button isn't pressed for nothing
+    let destination = match options.output {
+        Some(env_file) => Destination::EnvFile(env_file),
+        None => Destination::Config(free_config_path(config, profile)?),
+    };
+
+    let (base_uri, bridge_id) = match options.bridge {
+        Some(base_uri) => (base_uri, None),
+        None => {
+            let bridge = discover(options.candidate).await?;
+            (bridge.base_uri, Some(bridge.config.bridgeid))
+        }
+    };
+    let base_uri = match base_uri.strip_prefix("http://") {
+        Some(host) if options.https => format!("https://{}", host),
+        _ => base_uri,
+    };
+
+    // Registering is the first time the username goes over the wire, so that's when the certificate gets pinned
+    let trust = BridgeTrust {
+        bridge_id: bridge_id.clone(),
+        fingerprint: None,
+        known_bridges: KnownBridges::default_path().map(KnownBridges::new),
+    };
+
+    println!("Press the link button on the bridge at {}", base_uri);
+    let user_id = hoo_api::wait_for_registration(
+        &base_uri,
+        &options.devicetype,
+        &trust,
+        POLL_INTERVAL,
+        Duration::from_secs(options.timeout),
+    )
+    .await?;
+
+    match destination {
+        Destination::Config(path) => {
+            write_profile(&path, profile, &base_uri, &user_id, bridge_id.as_deref())?;
+            println!("Registered new user. Saved bridge and profile {:?} to {}", profile, path.display());
+            if Config::load(Some(&path))?.profile(None)?.name.as_deref() != Some(profile) {
+                println!("It isn't the default profile, so pass --profile {} to use it", profile);
+            }
+        }
+        Destination::EnvFile(path) => {
+            write_credentials(&path, &base_uri, &user_id)?;
+            println!("Registered new user. Credentials saved to {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+async fn discover(mut candidates: Vec<String>) -> Result<DiscoveredBridge> {
+    candidates.extend(DEFAULT_FALLBACK_URIS.iter().map(|uri| uri.to_string()));
+
+    println!("Searching for bridges...");
+    let mut bridges = hoo_api::discover_bridges(DISCOVERY_TIMEOUT, &candidates).await?;
+    if bridges.is_empty() {
+        return Err(anyhow!("No bridges found. Pass the bridge address with --bridge"));
+    }
+
+    for bridge in &bridges {
+        println!("Found {} ({}) at {}", bridge.config.name, bridge.config.bridgeid, bridge.base_uri);
+    }
+    if bridges.len() > 1 {
+        println!("Using the first bridge found. Pass --bridge to choose another");
+    }
+
+    Ok(bridges.remove(0))
+}
+
+/// The config file to add the profile to, as long as it doesn't already have a bridge or profile by that name
+fn free_config_path(config: Option<&Path>, profile: &str) -> Result<PathBuf> {
+    let path = config
+        .map(Path::to_path_buf)
+        .or_else(Config::default_path)
+        .ok_or_else(|| anyhow!("There's no config directory. Pass --config, or --output to write an env file"))?;
+
+    if path.exists() {
+        let existing = Config::load(Some(&path))?;
+        if existing.bridges.contains_key(profile) || existing.profiles.contains_key(profile) {
+            return Err(anyhow!(
+                "{} already has a bridge or profile called {:?}. Pick another name with --profile",
+                path.display(),
+                profile
+            ));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Appends the new bridge and profile, so everything already in the file stays as it was written
+fn write_profile(path: &Path, name: &str, base_uri: &str, user_id: &str, bridge_id: Option<&str>) -> Result<()> {
+    let mut bridge = Table::new();
+    bridge.insert("base_uri".to_string(), Value::from(base_uri));
+    bridge.insert("user_id".to_string(), Value::from(user_id));
+    if let Some(bridge_id) = bridge_id {
+        bridge.insert("bridge_id".to_string(), Value::from(bridge_id));
+    }
+    let mut profile = Table::new();
+    profile.insert("bridge".to_string(), Value::from(name));
+
+    let entries = [single("bridges", single(name, bridge)), single("profiles", single(name, profile))]
+        .iter()
+        .map(toml::to_string)
+        .collect::<Result<Vec<_>, _>>()?
+        .join("\n");
+
+    let mut contents = if path.exists() {
+        std::fs::read_to_string(path)?
+    } else {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        String::new()
+    };
+    if !contents.is_empty() {
+        contents.push_str(if contents.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    contents.push_str(&entries);
+
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+fn single(name: &str, value: Table) -> Table {
+    let mut table = Table::new();
+    table.insert(name.to_string(), Value::from(value));
+    table
+}
+
+// Replaces any existing credentials in the env file and keeps everything else
+fn write_credentials(path: &Path, base_uri: &str, user_id: &str) -> Result<()> {
+    let existing = if path.exists() {
+        std::fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| !line.starts_with("HUE_BASE_URI=") && !line.starts_with("HUE_USER_ID="))
+        .map(String::from)
+        .collect();
+    lines.push(format!("HUE_BASE_URI={}", base_uri));
+    lines.push(format!("HUE_USER_ID={}", user_id));
+
+    std::fs::write(path, lines.join("\n") + "\n")?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use rand::Rng;
+
+use hoo_api::{hash_token, LightSelector};
+
+/// Random bytes in a token, shown as twice as many hex characters
+const TOKEN_BYTES: usize = 32;
+
+/// Only the hash goes in the config file, so the token is shown once and can't be recovered later
+pub fn token(name: &str, control: bool, lights: Option<&LightSelector>) {
+    let mut rng = rand::thread_rng();
+    let token: String = (0..TOKEN_BYTES).map(|_| format!("{:02x}", rng.gen::<u8>())).collect();
+
+    println!("Token for {}: {}", name, token);
+    println!();
+    println!("Add this to a profile in the config file, e.g. for a profile called home:");
+    println!();
+    println!("[[profiles.home.tokens]]");
+    println!("name = {:?}", name);
+    println!("hash = {:?}", hash_token(&token));
+    println!("permission = {:?}", if control { "control" } else { "read" });
+    if let Some(lights) = lights {
+        println!("lights = {:?}", lights.to_string());
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+use std::process::Output;
+
+use tokio::process::Command;
+
+use hoo_api::MAX_SAT;
+use hoo_mock_bridge::{MockBridge, USER_ID};
+
+/// Runs `hoo` against the bridge from an empty directory, so no `.env` or config file gets in the way
+async fn hoo(bridge: &MockBridge, test: &str, args: &[&str]) -> Output {
+    let dir: PathBuf = std::env::temp_dir().join(format!("hoo_cli_{}_{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("config.toml");
+    std::fs::write(&config, "").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hoo"))
+        .args(args)
+        .current_dir(&dir)
+        .env("HUE_BASE_URI", bridge.base_uri())
+        .env("HUE_USER_ID", USER_ID)
+        .env("HOO_CONFIG", &config)
+        .env_remove("HOO_PROFILE")
+        .env_remove("HOO_BRIDGE_TIMEOUT")
+        .env_remove("HOO_BRIDGE_RETRIES")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .await
+        .unwrap();
+
+    std::fs::remove_dir_all(&dir).ok();
+    output
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[tokio::test]
+async fn turns_on_selected_lights() {
+    let bridge = MockBridge::with_sample_home();
+
+    let output = hoo(&bridge, "on", &["on", "group:living room"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(bridge.light(1).unwrap().state.is_on());
+    assert!(bridge.light(2).unwrap().state.is_on());
+}
+
+#[tokio::test]
+async fn sets_brightness() {
+    let bridge = MockBridge::with_sample_home();
+
+    let output = hoo(&bridge, "bri", &["bri", "name:kit*", "42"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(bridge.light(3).unwrap().state.bri, Some(42));
+}
+
+#[tokio::test]
+async fn sets_colors() {
+    let bridge = MockBridge::with_sample_home();
+
+    let output = hoo(&bridge, "hsb", &["hsb", "3", "1000", "200", "150"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let state = bridge.light(3).unwrap().state;
+    assert_eq!((state.hue, state.sat, state.bri), (Some(1000), Some(200), Some(150)));
+}
+
+#[tokio::test]
+async fn rgb_colors_are_fully_saturated() {
+    let bridge = MockBridge::with_sample_home();
+
+    let output = hoo(&bridge, "rgb", &["rgb", "1", "1", "0", "0"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = hoo(&bridge, "red", &["red", "3", "1"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(bridge.light(1).unwrap().state.sat, Some(MAX_SAT));
+    assert_eq!(bridge.light(3).unwrap().state.sat, Some(MAX_SAT));
+}
+
+#[tokio::test]
+async fn fails_when_nothing_matches() {
+    let bridge = MockBridge::with_sample_home();
+
+    let output = hoo(&bridge, "no_match", &["off", "7-9"]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("No lights match 7-9"), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn reports_lights_that_fail() {
+    let bridge = MockBridge::with_sample_home();
+    bridge.set_reachable(3, false);
+
+    let output = hoo(&bridge, "unreachable", &["off", "1,3"]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("1 of 2 lights couldn't be set"), "{}", stderr(&output));
+
+    assert!(!bridge.light(1).unwrap().state.is_on());
+    assert!(bridge.light(3).unwrap().state.is_on());
+}
+
+#[tokio::test]
+async fn rejects_a_zero_watch_interval() {
+    let bridge = MockBridge::with_sample_home();
+
+    let output = hoo(&bridge, "zero_interval", &["watch", "--interval", "0"]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--interval"), "{}", stderr(&output));
+    assert!(bridge.requests().is_empty());
+}
+
+#[tokio::test]
+async fn forgets_pinned_bridges() {
+    let dir = std::env::temp_dir().join(format!("hoo_cli_forget_{}", std::process::id()));
+    let known_bridges = dir.join("hoo").join("known_bridges.json");
+    std::fs::create_dir_all(known_bridges.parent().unwrap()).unwrap();
+    let pinned = serde_json::json!({
+        "192.168.1.20": { "bridge_id": "001788fffe4b5a6c", "fingerprint": "ab" },
+        "192.168.1.21": { "bridge_id": "001788fffe4b5a6d", "fingerprint": "cd" },
+    });
+    std::fs::write(&known_bridges, pinned.to_string()).unwrap();
+
+    let forget = |host: &'static str| {
+        Command::new(env!("CARGO_BIN_EXE_hoo"))
+            .args(["bridge", "forget", host])
+            .env("XDG_CONFIG_HOME", &dir)
+            .output()
+    };
+
+    let output = forget("https://192.168.1.20/").await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let remaining: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&known_bridges).unwrap()).unwrap();
+    assert!(remaining.get("192.168.1.20").is_none());
+    assert!(remaining.get("192.168.1.21").is_some());
+
+    let output = forget("192.168.1.20").await.unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("No certificate is pinned for 192.168.1.20"), "{}", stderr(&output));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn setup_adds_a_profile_to_the_config_file() {
+    let bridge = MockBridge::with_sample_home();
+    let dir = std::env::temp_dir().join(format!("hoo_cli_setup_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("hoo").join("config.toml");
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_hoo"))
+            .args(args)
+            .current_dir(&dir)
+            .env("XDG_CONFIG_HOME", &dir)
+            .env("HOO_CONFIG", &config)
+            .env_remove("HUE_BASE_URI")
+            .env_remove("HUE_USER_ID")
+            .env_remove("HOO_PROFILE")
+            .output()
+    };
+
+    let output = run(&["setup", "--bridge", &bridge.base_uri(), "--timeout", "1"]).await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let contents = std::fs::read_to_string(&config).unwrap();
+    assert!(contents.contains("[profiles.default]"), "{}", contents);
+
+    // The CLI picks the new profile up without any credentials in the environment
+    let output = run(&["on", "2"]).await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(bridge.light(2).unwrap().state.is_on());
+
+    let output = run(&["setup", "--bridge", &bridge.base_uri()]).await.unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("already has a bridge or profile called \"default\""), "{}", stderr(&output));
+
+    let output = run(&["--profile", "upstairs", "setup", "--bridge", &bridge.base_uri()]).await.unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("pass --profile upstairs"));
+    // Appended, so what was there before is untouched
+    assert!(std::fs::read_to_string(&config).unwrap().starts_with(&contents));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -1,5 +1,5 @@
 import React from 'react';
-import { Light, HooLight, FakeLight } from './common/types/light';
+import { Light, HooLight, FakeLight, LightEvent } from './common/types/light';
 import LightControls from './components/LightControls';
 import AnimationControls from './components/AnimationControls';
 import * as LightApi from './common/api/lights';
@@ -18,8 +18,20 @@ export default class Hoo extends React.Component<{}, HooState> {
         }
     }
 
+    private unsubscribe?: () => void;
+
     async componentDidMount() {
-        const lights = await LightApi.getAllLights();
+        this.setLights(await LightApi.getAllLights());
+        this.unsubscribe = LightApi.subscribe(this.setLights, this.applyEvent);
+    }
+
+    componentWillUnmount() {
+        if (this.unsubscribe) {
+            this.unsubscribe();
+        }
+    }
+
+    setLights = (lights: HooLight[]) => {
         const lightStates = [];
         for (const lightNum in lights) {
             const lightNumber = parseInt(lightNum, 10);
@@ -33,6 +45,24 @@ export default class Hoo extends React.Component<{}, HooState> {
         this.setState({lights: lightStates});
     }
 
+    applyEvent = async (event: LightEvent) => {
+        switch (event.event) {
+            case 'added':
+                this.setLights(await LightApi.getAllLights());
+                break;
+            case 'removed':
+                this.setState({lights: this.state.lights.filter(light => light.number !== event.light)});
+                break;
+            default: {
+                const light = this.state.lights.find(light => light.number === event.light);
+                if (light instanceof HooLight) {
+                    light.apply(event);
+                    this.setState({lights: [...this.state.lights]});
+                }
+            }
+        }
+    }
+
     render() {
         const lightControls = this.state.lights.map(light =>
             <li key={light.number}>
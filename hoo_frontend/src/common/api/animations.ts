@@ -1,16 +1,17 @@
 import { BASE_URL } from '../constants';
+import { checked } from './errors';
 
 export async function rotate(transitionTime: number, holdTime: number): Promise<void> {
     const url = `${BASE_URL}/rotate/${transitionTime}/${holdTime}`;
-    await fetch(url);
+    await checked(await fetch(url, { method: 'PUT' }));
 }
 
 export async function random(transitionTime: number, holdTime: number): Promise<void> {
     const url = `${BASE_URL}/random/${transitionTime}/${holdTime}`;
-    await fetch(url);
+    await checked(await fetch(url, { method: 'PUT' }));
 }
 
 export async function stop(): Promise<void> {
     const url = `${BASE_URL}/stop`;
-    await fetch(url);
+    await checked(await fetch(url, { method: 'PUT' }));
 }
@@ -0,0 +1,27 @@
+export interface ApiError {
+    code: string;
+    message: string;
+    bridge?: {
+        type: number;
+        address: string;
+        description: string;
+    };
+}
+
+export class HooApiError extends Error {
+    public readonly status: number;
+    public readonly error: ApiError;
+
+    constructor(status: number, error: ApiError) {
+        super(error.message);
+        this.status = status;
+        this.error = error;
+    }
+}
+
+export async function checked(response: Response): Promise<Response> {
+    if (!response.ok) {
+        throw new HooApiError(response.status, await response.json());
+    }
+    return response;
+}
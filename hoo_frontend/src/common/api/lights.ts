@@ -1,41 +1,59 @@
 import { BASE_URL } from '../constants';
-import { Light, HooLight } from '../types/light';
+import { checked } from './errors';
+import { Light, HooLight, LightEvent, LightState } from '../types/light';
 
 export async function getAllLights(): Promise<HooLight[]> {
     const url = `${BASE_URL}/lights`;
-    const response = await fetch(url);
+    const response = await checked(await fetch(url));
     const lights: HooLight[] = await response.json();
     return lights;
 }
 
 export async function getLight(lightNumber: number): Promise<HooLight> {
     const url = `${BASE_URL}/light/${lightNumber}`;
-    const response: any = await fetch(url);
+    const response: any = await checked(await fetch(url));
     const light: HooLight = await response.json();
     return light;
 }
 
+// The server sends every light when the connection opens and again if this client falls behind
+export function subscribe(onLights: (lights: HooLight[]) => void, onChange: (event: LightEvent) => void): () => void {
+    const source = new EventSource(`${BASE_URL}/events`);
+    source.addEventListener('lights', (message) => onLights(JSON.parse((message as MessageEvent).data)));
+    source.addEventListener('light', (message) => onChange(JSON.parse((message as MessageEvent).data)));
+    return () => source.close();
+}
+
 export async function on(lightNumber: number) {
     const url = `${BASE_URL}/light/${lightNumber}/on`;
-    await fetch(url, { method: 'PUT' });
+    await checked(await fetch(url, { method: 'PUT' }));
 }
 
 export async function off(lightNumber: number) {
     const url = `${BASE_URL}/light/${lightNumber}/off`;
-    await fetch(url, { method: 'PUT' });
+    await checked(await fetch(url, { method: 'PUT' }));
 }
 
 export async function setBrightness(lightNumber: number, brightness: number) {
     const url = `${BASE_URL}/light/${lightNumber}/state?bri=${brightness}`;
-    await fetch(url, { method: 'PUT' });
+    await checked(await fetch(url, { method: 'PUT' }));
 }
 
 export async function setSaturation(lightNumber: number, saturation: number) {
     const url = `${BASE_URL}/light/${lightNumber}/state?sat=${saturation}`;
-    await fetch(url, { method: 'PUT' });
+    await checked(await fetch(url, { method: 'PUT' }));
 }
 
 export async function setHue(lightNumber: number, hue: number) {
     const url = `${BASE_URL}/light/${lightNumber}/state?hue=${hue}`;
-    await fetch(url, { method: 'PUT' });
+    await checked(await fetch(url, { method: 'PUT' }));
+}
+
+export async function setState(lightNumber: number, state: Partial<LightState>) {
+    const url = `${BASE_URL}/light/${lightNumber}/state`;
+    await checked(await fetch(url, {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(state),
+    }));
 }
@@ -1,4 +1,5 @@
 declare let process: any;
 
-export const BASE_URL = `http://localhost:8000/api`;
+// Relative so it works when hoo_server serves the app. `npm start` proxies it to the server, see package.json.
+export const BASE_URL = process.env.REACT_APP_API_URL || `/api`;
 export const INPUT_THROTTLING_DELAY = 100;
@@ -34,6 +34,26 @@ export class HooLight implements Light {
         this.state = updatedLight.state;
     }
 
+    public apply(event: LightEvent) {
+        switch (event.event) {
+            case 'power':
+                this.state.on = event.on;
+                break;
+            case 'brightness':
+                this.state.bri = event.bri;
+                break;
+            case 'color':
+                if (event.hue !== null) this.state.hue = event.hue;
+                if (event.sat !== null) this.state.sat = event.sat;
+                if (event.xy !== null) this.state.xy = event.xy;
+                if (event.ct !== null) this.state.ct = event.ct;
+                break;
+            case 'reachability':
+                this.state.reachable = event.reachable;
+                break;
+        }
+    }
+
     public get isOn(): boolean {
         return this.state.on;
     }
@@ -142,12 +162,30 @@ export interface LightState {
     bri: number;
     xy: [number, number];
     ct: number;
-    effect: string;
-    transitionTime: number;
+    effect: 'none' | 'colorloop';
+    alert: 'none' | 'select' | 'lselect';
+    transitiontime: number;
     hue_inc: number;
     sat_inc: number;
     bri_inc: number;
     ct_inc: number;
     xy_inc: [number, number];
+    colormode: 'hs' | 'xy' | 'ct';
     reachable: boolean;
 }
+
+export type LightEvent =
+    | { event: 'added', light: number, name: string }
+    | { event: 'removed', light: number }
+    | { event: 'power', light: number, on: boolean }
+    | { event: 'brightness', light: number, bri: number }
+    | {
+        event: 'color',
+        light: number,
+        colormode: 'hs' | 'xy' | 'ct' | null,
+        hue: number | null,
+        sat: number | null,
+        xy: [number, number] | null,
+        ct: number | null,
+    }
+    | { event: 'reachability', light: number, reachable: boolean };
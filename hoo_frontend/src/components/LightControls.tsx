@@ -68,7 +68,7 @@ export default class LightControls extends React.Component<LightControlsProps, L
                 id="sat"
                 type="range"
                 min="0"
-                max="255"
+                max="254"
                 // value={this.state.light.saturation}
                 onChange={this.setSat}
               />
@@ -78,8 +78,8 @@ export default class LightControls extends React.Component<LightControlsProps, L
               <input
                 id="bri"
                 type="range"
-                min="0"
-                max="255"
+                min="1"
+                max="254"
                 // value={this.state.light.brightness}
                 onChange={this.setBri}
               />
@@ -91,8 +91,8 @@ export default class LightControls extends React.Component<LightControlsProps, L
 
     previewFillColor(): string {
         const h = (this.state.light.hue / 65535) * 360;
-        const s = (this.state.light.saturation / 255) * 100;
-        const l = (this.state.light.brightness / 255) * 100;
+        const s = (this.state.light.saturation / 254) * 100;
+        const l = (this.state.light.brightness / 254) * 100;
 
         return `hsl(${h}, ${s}%, ${l}%)`;
     }
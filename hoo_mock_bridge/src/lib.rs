@@ -0,0 +1,301 @@
+//! An in-process fake Hue bridge for testing without a real one. It keeps lights and groups in memory, answers
+//! the bridge API's light and group endpoints the way a bridge does, registers users as if its link button had just
+//! been pressed, and can be told to be slow, fail requests or have lights drop off the network. It can also serve HTTPS with a self-signed certificate, like newer bridges.
+//!
+//! ```ignore
+//! let bridge = MockBridge::start();
+//! bridge.add_light(1, "Desk", LightState::new().on(true));
+//! let client = HueClient::new(&bridge.base_uri(), hoo_mock_bridge::USER_ID);
+//! ```
+
+mod state;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::oneshot;
+use warp::http::{Method, Response, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::hyper::Body;
+use warp::path::Tail;
+use warp::Filter;
+
+use hoo_api_types::{Group, GroupAction, GroupNumber, GroupType, Light, LightNumber, LightState};
+
+use state::BridgeState;
+
+/// The only user the bridge accepts. Any other gets an unauthorized user error.
+pub const USER_ID: &str = "mock-user";
+
+/// The bridge id in the common name of both test certificates
+pub const BRIDGE_ID: &str = "001788fffe4b5a6c";
+
+/// A self-signed certificate for `start_https` to serve, like the ones real bridges have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockCertificate {
+    Bridge,
+    /// For the same bridge id but a different key, like a bridge that was reset, or one pretending to be another
+    Replacement,
+}
+
+impl MockCertificate {
+    fn pem(self) -> (&'static str, &'static str) {
+        match self {
+            MockCertificate::Bridge => (include_str!("../certs/bridge.pem"), include_str!("../certs/bridge.key")),
+            MockCertificate::Replacement => (
+                include_str!("../certs/replacement.pem"),
+                include_str!("../certs/replacement.key"),
+            ),
+        }
+    }
+}
+
+/// A way for the next request to fail, instead of being answered
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// A bridge error in a 200 response, like `{"type":901,"description":"Internal error, 404"}`
+    Bridge { kind: u16, description: String },
+    /// An HTTP status with an empty body
+    Status(u16),
+    /// Answered as usual, but only after this long, like a bridge that's stopped responding
+    Slow(Duration),
+    /// The status and headers are sent straight away, but the body only after this long, like a bridge that stalls
+    /// partway through answering
+    SlowBody(Duration),
+}
+
+/// A request the bridge got, with the path after the user id, e.g. `/lights/1/state`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+/// A running fake bridge. It stops when dropped.
+#[derive(Debug)]
+pub struct MockBridge {
+    state: Arc<Mutex<BridgeState>>,
+    addr: SocketAddr,
+    https: bool,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockBridge {
+    /// Starts a bridge with no lights or groups on a free local port. Has to be called from inside a tokio runtime.
+    pub fn start() -> Self {
+        Self::start_with(None)
+    }
+
+    /// Like `start`, but the bridge only speaks HTTPS and presents `certificate`
+    pub fn start_https(certificate: MockCertificate) -> Self {
+        Self::start_with(Some(certificate))
+    }
+
+    /// Starts a bridge with the lights and groups most tests want, see `add_sample_home`
+    pub fn with_sample_home() -> Self {
+        let bridge = Self::start();
+        bridge.add_sample_home();
+        bridge
+    }
+
+    fn start_with(certificate: Option<MockCertificate>) -> Self {
+        let state = Arc::new(Mutex::new(BridgeState::default()));
+
+        // Every registration gets the one user the bridge accepts
+        let register = warp::path!("api")
+            .and(warp::post())
+            .map(|| warp::reply::json(&json!([{ "success": { "username": USER_ID } }])));
+
+        let state_clone = state.clone();
+        let routes = warp::path("api")
+            .and(warp::path::param::<String>())
+            .and(warp::path::tail())
+            .and(warp::method())
+            .and(warp::body::bytes())
+            .and_then(move |user_id, path, method, body| handle(state_clone.clone(), user_id, path, method, body));
+        let routes = register.or(routes);
+
+        let (shutdown, shutdown_signal) = oneshot::channel();
+        let shutdown_signal = async {
+            shutdown_signal.await.ok();
+        };
+        let addr = match certificate {
+            Some(certificate) => {
+                let (cert, key) = certificate.pem();
+                let (addr, server) = warp::serve(routes)
+                    .tls()
+                    .cert(cert)
+                    .key(key)
+                    .bind_with_graceful_shutdown(([127, 0, 0, 1], 0), shutdown_signal);
+                tokio::spawn(server);
+                addr
+            }
+            None => {
+                let (addr, server) =
+                    warp::serve(routes).bind_with_graceful_shutdown(([127, 0, 0, 1], 0), shutdown_signal);
+                tokio::spawn(server);
+                addr
+            }
+        };
+
+        Self {
+            state,
+            addr,
+            https: certificate.is_some(),
+            shutdown: Some(shutdown),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// What to give `HueClient::new`, along with `USER_ID`
+    pub fn base_uri(&self) -> String {
+        let scheme = if self.https { "https" } else { "http" };
+        format!("{}://{}", scheme, self.addr)
+    }
+
+    /// Adds Desk (on), Lamp (off) and Kitchen (on) lights, all at brightness 100 but Kitchen at 254, with Desk and
+    /// Lamp in a Living room group
+    pub fn add_sample_home(&self) {
+        self.add_light(1, "Desk", LightState::new().on(true).bri(100));
+        self.add_light(2, "Lamp", LightState::new().on(false).bri(100));
+        self.add_light(3, "Kitchen", LightState::new().on(true).bri(254));
+        self.add_group(1, "Living room", &[1, 2]);
+    }
+
+    /// Adds or replaces a light. It's reachable unless the state says otherwise.
+    pub fn add_light(&self, light_num: LightNumber, name: &str, state: LightState) {
+        let mut state = state;
+        state.reachable = state.reachable.or(Some(true));
+        let light = Light {
+            name: name.to_string(),
+            state,
+        };
+        self.state.lock().unwrap().lights.insert(light_num, light);
+    }
+
+    pub fn remove_light(&self, light_num: LightNumber) {
+        self.state.lock().unwrap().lights.remove(&light_num);
+    }
+
+    /// Adds or replaces a room
+    pub fn add_group(&self, group_num: GroupNumber, name: &str, lights: &[LightNumber]) {
+        let group = Group {
+            name: name.to_string(),
+            lights: lights.to_vec(),
+            group_type: GroupType::Room,
+            class: None,
+            action: GroupAction::new(),
+            state: None,
+        };
+        self.state.lock().unwrap().groups.insert(group_num, group);
+    }
+
+    pub fn light(&self, light_num: LightNumber) -> Option<Light> {
+        self.state.lock().unwrap().lights.get(&light_num).cloned()
+    }
+
+    pub fn group(&self, group_num: GroupNumber) -> Option<Group> {
+        self.state.lock().unwrap().groups.get(&group_num).cloned()
+    }
+
+    /// Unreachable lights reject every change with a type 304 error
+    pub fn set_reachable(&self, light_num: LightNumber, reachable: bool) {
+        if let Some(light) = self.state.lock().unwrap().lights.get_mut(&light_num) {
+            light.state.reachable = Some(reachable);
+        }
+    }
+
+    /// How long to wait before answering each request
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// Fails the next request that hasn't already been given a failure. Calling this more than once fails that
+    /// many requests in a row.
+    pub fn fail_next(&self, failure: Failure) {
+        self.state.lock().unwrap().failures.push_back(failure);
+    }
+
+    /// Every request so far, oldest first
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+}
+
+impl Drop for MockBridge {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<BridgeState>>,
+    user_id: String,
+    path: Tail,
+    method: Method,
+    body: Bytes,
+) -> Result<Box<dyn warp::Reply>, Infallible> {
+    let path = format!("/{}", path.as_str().trim_end_matches('/'));
+    let (latency, failure) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(RecordedRequest {
+            method: method.to_string(),
+            path: path.clone(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+        (state.latency, state.failures.pop_front())
+    };
+
+    if latency > Duration::from_millis(0) {
+        tokio::time::delay_for(latency).await;
+    }
+    if let Some(Failure::Slow(delay)) = failure {
+        tokio::time::delay_for(delay).await;
+    }
+    let body_delay = match failure {
+        Some(Failure::SlowBody(delay)) => Some(delay),
+        _ => None,
+    };
+
+    let response = match failure {
+        Some(Failure::Status(status)) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Ok(Box::new(status));
+        }
+        Some(Failure::Bridge { kind, description }) => json!([state::error(kind, &path, description)]),
+        Some(Failure::Slow(_)) | Some(Failure::SlowBody(_)) | None if user_id != USER_ID => {
+            json!([state::error(1, &path, "unauthorized user".to_string())])
+        }
+        Some(Failure::Slow(_)) | Some(Failure::SlowBody(_)) | None => {
+            state.lock().unwrap().handle(method.as_str(), &path, &body)
+        }
+    };
+
+    if let Some(delay) = body_delay {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            tokio::time::delay_for(delay).await;
+            sender.send_data(Bytes::from(response.to_string())).await.ok();
+        });
+        let response = Response::builder()
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap();
+        return Ok(Box::new(response));
+    }
+
+    Ok(Box::new(warp::reply::json(&response)))
+}
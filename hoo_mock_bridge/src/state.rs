@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use serde_json::{json, Map, Value};
+
+use hoo_api_types::{
+    Group, GroupAction, GroupAttributes, GroupNumber, GroupState, GroupType, Light, LightAlert, LightColorMode,
+    LightEffect, LightNumber, LightState,
+};
+
+use crate::{Failure, RecordedRequest};
+
+/// Resources the mock only has empty collections of
+const EMPTY_RESOURCES: &[&str] = &["scenes", "schedules", "sensors", "rules"];
+
+/// Attributes that can be changed while a light is off
+const ALWAYS_MODIFIABLE: &[&str] = &["on", "transitiontime", "alert"];
+
+/// Everything the bridge knows about, and how it's been told to misbehave
+#[derive(Debug, Default)]
+pub(crate) struct BridgeState {
+    pub lights: BTreeMap<LightNumber, Light>,
+    pub groups: BTreeMap<GroupNumber, Group>,
+    pub failures: VecDeque<Failure>,
+    pub latency: Duration,
+    pub requests: Vec<RecordedRequest>,
+}
+
+/// Why a single attribute of a state change was rejected
+enum Rejected {
+    NotAvailable,
+    InvalidValue,
+}
+
+impl BridgeState {
+    /// Answers a request for `path`, the part of the URI after the user id, the way a bridge would. Errors are
+    /// reported in the body with a 200, like a real bridge does.
+    pub fn handle(&mut self, method: &str, path: &str, body: &[u8]) -> Value {
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        match (method, segments.as_slice()) {
+            ("GET", ["lights"]) => json!(self.lights),
+            ("GET", ["lights", light_num]) => match self.light_num(light_num) {
+                Some(light_num) => json!(self.lights[&light_num]),
+                None => not_available(path),
+            },
+            ("PUT", ["lights", light_num, "state"]) => match self.light_num(light_num) {
+                Some(light_num) => self.set_light_state(light_num, body),
+                None => not_available(path),
+            },
+            ("GET", ["groups"]) => {
+                let groups: BTreeMap<_, _> = self.groups.keys().map(|group_num| (*group_num, self.group(*group_num))).collect();
+                json!(groups)
+            }
+            ("GET", ["groups", group_num]) => match self.group_num(group_num, true) {
+                Some(group_num) => json!(self.group(group_num)),
+                None => not_available(path),
+            },
+            ("POST", ["groups"]) => self.create_group(body),
+            ("PUT", ["groups", group_num]) => match self.group_num(group_num, false) {
+                Some(group_num) => self.update_group(group_num, body),
+                None => not_available(path),
+            },
+            ("DELETE", ["groups", group_num]) => match self.group_num(group_num, false) {
+                Some(group_num) => {
+                    self.groups.remove(&group_num);
+                    json!([{ "success": format!("/groups/{} deleted", group_num) }])
+                }
+                None => not_available(path),
+            },
+            ("PUT", ["groups", group_num, "action"]) => match self.group_num(group_num, true) {
+                Some(group_num) => self.set_group_action(group_num, body),
+                None => not_available(path),
+            },
+            ("GET", [resource]) if EMPTY_RESOURCES.contains(resource) => json!({}),
+            ("GET", _) => not_available(path),
+            _ => json!([error(
+                4,
+                path,
+                format!("method, {}, not available for resource, {}", method, path)
+            )]),
+        }
+    }
+
+    fn light_num(&self, light_num: &str) -> Option<LightNumber> {
+        light_num.parse().ok().filter(|light_num| self.lights.contains_key(light_num))
+    }
+
+    /// Group 0, which holds every light, can be read and set but not changed
+    fn group_num(&self, group_num: &str, allow_zero: bool) -> Option<GroupNumber> {
+        let group_num = group_num.parse().ok()?;
+        if (group_num == 0 && allow_zero) || self.groups.contains_key(&group_num) {
+            Some(group_num)
+        } else {
+            None
+        }
+    }
+
+    /// A group with its state worked out from its lights
+    fn group(&self, group_num: GroupNumber) -> Group {
+        let mut group = match self.groups.get(&group_num) {
+            Some(group) => group.clone(),
+            None => Group {
+                name: "Group 0".to_string(),
+                lights: self.lights.keys().copied().collect(),
+                group_type: GroupType::LightGroup,
+                class: None,
+                action: GroupAction::new(),
+                state: None,
+            },
+        };
+
+        let on: Vec<bool> = group
+            .lights
+            .iter()
+            .filter_map(|light_num| self.lights.get(light_num))
+            .map(|light| light.state.is_on())
+            .collect();
+        group.state = Some(GroupState {
+            all_on: !on.is_empty() && on.iter().all(|on| *on),
+            any_on: on.iter().any(|on| *on),
+        });
+        group
+    }
+
+    fn set_light_state(&mut self, light_num: LightNumber, body: &[u8]) -> Value {
+        let address = format!("/lights/{}/state", light_num);
+        let changes = match parse_object(body) {
+            Some(changes) => changes,
+            None => return invalid_json(&address),
+        };
+
+        let light = self.lights.get_mut(&light_num).expect("light numbers are checked before this");
+        let turning_on = changes.get("on") == Some(&Value::Bool(true));
+        let results: Vec<Value> = changes
+            .iter()
+            .map(|(attribute, value)| {
+                let address = format!("{}/{}", address, attribute);
+                if !light.state.is_reachable() {
+                    return error(
+                        304,
+                        &address,
+                        format!("parameter, {}, is not modifiable. Device is unreachable.", attribute),
+                    );
+                }
+                if !light.state.is_on() && !turning_on && !ALWAYS_MODIFIABLE.contains(&attribute.as_str()) {
+                    return error(
+                        201,
+                        &address,
+                        format!("parameter, {}, is not modifiable. Device is set to off.", attribute),
+                    );
+                }
+                attribute_result(&mut light.state, &address, attribute, value)
+            })
+            .collect();
+
+        Value::Array(results)
+    }
+
+    /// Sets every reachable light in the group, whether it's on or not
+    fn set_group_action(&mut self, group_num: GroupNumber, body: &[u8]) -> Value {
+        let address = format!("/groups/{}/action", group_num);
+        let changes = match parse_object(body) {
+            Some(changes) => changes,
+            None => return invalid_json(&address),
+        };
+
+        let light_nums = self.group(group_num).lights;
+        let mut results = Vec::new();
+        for (attribute, value) in &changes {
+            let address = format!("{}/{}", address, attribute);
+            if attribute == "scene" {
+                let scene = value.as_str().unwrap_or_default();
+                results.push(error(3, &address, format!("resource, /scenes/{}, not available", scene)));
+                continue;
+            }
+
+            let mut action = self.groups.get(&group_num).map(|group| group.action.state.clone()).unwrap_or_default();
+            let result = attribute_result(&mut action, &address, attribute, value);
+            if result.get("success").is_some() {
+                if let Some(group) = self.groups.get_mut(&group_num) {
+                    group.action.state = action;
+                }
+                for light_num in &light_nums {
+                    if let Some(light) = self.lights.get_mut(light_num).filter(|light| light.state.is_reachable()) {
+                        let _ = apply(&mut light.state, attribute, value);
+                    }
+                }
+            }
+            results.push(result);
+        }
+
+        Value::Array(results)
+    }
+
+    fn create_group(&mut self, body: &[u8]) -> Value {
+        let attributes: GroupAttributes = match serde_json::from_slice(body) {
+            Ok(attributes) => attributes,
+            Err(_) => return invalid_json("/groups"),
+        };
+        let group_type = attributes.group_type.unwrap_or(GroupType::LightGroup);
+        if attributes.lights.is_empty() && group_type == GroupType::LightGroup {
+            return json!([error(5, "/groups", "invalid/missing parameters in body".to_string())]);
+        }
+        if let Some(light_num) = attributes.lights.iter().find(|light_num| !self.lights.contains_key(light_num)) {
+            return json!([error(7, "/groups/lights", format!("invalid value, {}, for parameter, lights", light_num))]);
+        }
+
+        let group_num = match (1..=GroupNumber::MAX).find(|group_num| !self.groups.contains_key(group_num)) {
+            Some(group_num) => group_num,
+            None => return json!([error(301, "/groups", "group could not be created. Group table is full.".to_string())]),
+        };
+        let group = Group {
+            name: attributes.name.unwrap_or_else(|| format!("Group {}", group_num)),
+            lights: attributes.lights,
+            group_type,
+            class: attributes.class,
+            action: GroupAction::new(),
+            state: None,
+        };
+        self.groups.insert(group_num, group);
+
+        json!([{ "success": { "id": group_num.to_string() } }])
+    }
+
+    fn update_group(&mut self, group_num: GroupNumber, body: &[u8]) -> Value {
+        let address = format!("/groups/{}", group_num);
+        let attributes: GroupAttributes = match serde_json::from_slice(body) {
+            Ok(attributes) => attributes,
+            Err(_) => return invalid_json(&address),
+        };
+
+        let unknown_light = attributes.lights.iter().find(|light_num| !self.lights.contains_key(light_num)).copied();
+        let group = self.groups.get_mut(&group_num).expect("group numbers are checked before this");
+        let mut results = Vec::new();
+        if let Some(name) = attributes.name {
+            results.push(json!({ "success": { format!("{}/name", address): name } }));
+            group.name = name;
+        }
+        if !attributes.lights.is_empty() {
+            let address = format!("{}/lights", address);
+            match unknown_light {
+                Some(light_num) => results.push(error(
+                    7,
+                    &address,
+                    format!("invalid value, {}, for parameter, lights", light_num),
+                )),
+                None => {
+                    let lights: Vec<String> = attributes.lights.iter().map(ToString::to_string).collect();
+                    results.push(json!({ "success": { address: lights } }));
+                    group.lights = attributes.lights;
+                }
+            }
+        }
+        if let Some(class) = attributes.class {
+            results.push(json!({ "success": { format!("{}/class", address): class } }));
+            group.class = Some(class);
+        }
+        if attributes.group_type.is_some() {
+            results.push(error(8, &format!("{}/type", address), "parameter, type, is not modifiable".to_string()));
+        }
+
+        Value::Array(results)
+    }
+}
+
+fn attribute_result(state: &mut LightState, address: &str, attribute: &str, value: &Value) -> Value {
+    match apply(state, attribute, value) {
+        Ok(()) => json!({ "success": { address: value } }),
+        Err(Rejected::NotAvailable) => error(6, address, format!("parameter, {}, not available", attribute)),
+        Err(Rejected::InvalidValue) => error(
+            7,
+            address,
+            format!("invalid value, {}, for parameter, {}", value, attribute),
+        ),
+    }
+}
+
+/// Changes one attribute of a light's state, using the ranges a bridge accepts. Increments are clamped to the
+/// range, except for hue, which wraps around.
+fn apply(state: &mut LightState, attribute: &str, value: &Value) -> Result<(), Rejected> {
+    match attribute {
+        "on" => state.on = Some(value.as_bool().ok_or(Rejected::InvalidValue)?),
+        "bri" => state.bri = Some(int_in(value, 1, 254)? as u8),
+        "hue" => {
+            state.hue = Some(int_in(value, 0, 65535)? as u16);
+            state.colormode = Some(LightColorMode::HS);
+        }
+        "sat" => {
+            state.sat = Some(int_in(value, 0, 254)? as u8);
+            state.colormode = Some(LightColorMode::HS);
+        }
+        "ct" => {
+            state.ct = Some(int_in(value, 153, 500)? as u16);
+            state.colormode = Some(LightColorMode::CT);
+        }
+        "xy" => {
+            state.xy = Some(pair_in(value, 0.0, 1.0)?);
+            state.colormode = Some(LightColorMode::XY);
+        }
+        "effect" => {
+            state.effect = Some(serde_json::from_value::<LightEffect>(value.clone()).map_err(|_| Rejected::InvalidValue)?)
+        }
+        "alert" => {
+            state.alert = Some(serde_json::from_value::<LightAlert>(value.clone()).map_err(|_| Rejected::InvalidValue)?)
+        }
+        // Only affects how the change is made, so there's nothing to store
+        "transitiontime" => {
+            int_in(value, 0, 65535)?;
+        }
+        "bri_inc" => {
+            let bri = i64::from(state.bri.unwrap_or(1)) + int_in(value, -254, 254)?;
+            state.bri = Some(bri.clamp(1, 254) as u8);
+        }
+        "sat_inc" => {
+            let sat = i64::from(state.sat.unwrap_or_default()) + int_in(value, -254, 254)?;
+            state.sat = Some(sat.clamp(0, 254) as u8);
+            state.colormode = Some(LightColorMode::HS);
+        }
+        "hue_inc" => {
+            let hue = i64::from(state.hue.unwrap_or_default()) + int_in(value, -65534, 65534)?;
+            state.hue = Some(hue.rem_euclid(65536) as u16);
+            state.colormode = Some(LightColorMode::HS);
+        }
+        "ct_inc" => {
+            let ct = i64::from(state.ct.unwrap_or(153)) + int_in(value, -65534, 65534)?;
+            state.ct = Some(ct.clamp(153, 500) as u16);
+            state.colormode = Some(LightColorMode::CT);
+        }
+        "xy_inc" => {
+            let (x_inc, y_inc) = pair_in(value, -0.5, 0.5)?;
+            let (x, y) = state.xy.unwrap_or_default();
+            state.xy = Some(((x + x_inc).clamp(0.0, 1.0), (y + y_inc).clamp(0.0, 1.0)));
+            state.colormode = Some(LightColorMode::XY);
+        }
+        _ => return Err(Rejected::NotAvailable),
+    }
+
+    Ok(())
+}
+
+fn int_in(value: &Value, min: i64, max: i64) -> Result<i64, Rejected> {
+    value.as_i64().filter(|value| (min..=max).contains(value)).ok_or(Rejected::InvalidValue)
+}
+
+fn pair_in(value: &Value, min: f64, max: f64) -> Result<(f32, f32), Rejected> {
+    let in_range = |value: &Value| value.as_f64().filter(|value| (min..=max).contains(value));
+    match value.as_array().map(Vec::as_slice) {
+        Some([x, y]) => match (in_range(x), in_range(y)) {
+            (Some(x), Some(y)) => Ok((x as f32, y as f32)),
+            _ => Err(Rejected::InvalidValue),
+        },
+        _ => Err(Rejected::InvalidValue),
+    }
+}
+
+fn parse_object(body: &[u8]) -> Option<Map<String, Value>> {
+    match serde_json::from_slice(body) {
+        Ok(Value::Object(object)) => Some(object),
+        _ => None,
+    }
+}
+
+/// One entry of the array a bridge answers with, e.g.
+/// `{"error":{"type":3,"address":"/lights/9","description":"resource, /lights/9, not available"}}`
+pub(crate) fn error(kind: u16, address: &str, description: String) -> Value {
+    json!({ "error": { "type": kind, "address": address, "description": description } })
+}
+
+fn not_available(path: &str) -> Value {
+    json!([error(3, path, format!("resource, {}, not available", path))])
+}
+
+fn invalid_json(address: &str) -> Value {
+    json!([error(2, address, "body contains invalid json".to_string())])
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use hoo_api::{LightBackend, LightNumber, LightState, WriteQueue, MAX_SAT};
+
+// The bridge can't keep up with back-to-back updates, so never step faster than this
+const MIN_STEP_MILLIS: u64 = 100;
+
+pub type AnimationSender = UnboundedSender<AnimationMessage>;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "animation", rename_all = "lowercase")]
+pub enum AnimationMessage {
+    Rotate { transition_time: u16, hold_time: u16 },
+    Random { transition_time: u16, hold_time: u16 },
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AnimationKind {
+    Rotate,
+    Random,
+}
+
+struct Animation {
+    kind: AnimationKind,
+    transition_time: u16,
+    hold_time: u16,
+    light_numbers: Vec<LightNumber>,
+    states: Vec<LightState>,
+}
+
+impl Animation {
+    async fn new<B: LightBackend>(
+        backend: &B,
+        kind: AnimationKind,
+        transition_time: u16,
+        hold_time: u16,
+    ) -> Result<Self> {
+        let mut lights: Vec<_> = backend.get_active_lights().await?.into_iter().collect();
+        lights.sort_by_key(|(light_num, _)| *light_num);
+
+        let (light_numbers, states) = lights
+            .into_iter()
+            .map(|(light_num, light)| {
+                let state = LightState {
+                    hue: light.state.hue,
+                    sat: light.state.sat,
+                    bri: light.state.bri,
+                    ..LightState::default()
+                };
+                (light_num, state)
+            })
+            .unzip();
+
+        Ok(Self {
+            kind,
+            transition_time,
+            hold_time,
+            light_numbers,
+            states,
+        })
+    }
+
+    /// Changes are queued rather than awaited so a step that outpaces the bridge merges into the previous one
+    fn step(&mut self, queue: &WriteQueue) {
+        match self.kind {
+            AnimationKind::Rotate => {
+                if !self.states.is_empty() {
+                    self.states.rotate_left(1);
+                }
+            }
+            AnimationKind::Random => {
+                for state in self.states.iter_mut() {
+                    *state = LightState::new().hue(rand::random()).sat(MAX_SAT);
+                }
+            }
+        }
+
+        for (light_num, state) in self.light_numbers.iter().zip(&self.states) {
+            let state = state.clone().transitiontime(self.transition_time);
+            let write = queue.set_state(*light_num, &state);
+            let light_num = *light_num;
+            tokio::spawn(async move {
+                if let Err(e) = write.await {
+                    eprintln!("Animation step failed for light {}: {}", light_num, e);
+                }
+            });
+        }
+    }
+
+    // Transition and hold times are in deciseconds, the same unit the bridge uses
+    fn step_duration(&self) -> Duration {
+        let deciseconds = u64::from(self.transition_time) + u64::from(self.hold_time);
+        Duration::from_millis((deciseconds * 100).max(MIN_STEP_MILLIS))
+    }
+}
+
+pub fn spawn<B: LightBackend>(backend: B, queue: WriteQueue) -> AnimationSender {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(run(backend, queue, receiver));
+    sender
+}
+
+async fn run<B: LightBackend>(backend: B, queue: WriteQueue, mut receiver: UnboundedReceiver<AnimationMessage>) {
+    let mut animation: Option<Animation> = None;
+
+    loop {
+        let message = match animation.as_mut() {
+            Some(current) => {
+                current.step(&queue);
+
+                tokio::select! {
+                    message = receiver.recv() => message,
+                    _ = tokio::time::delay_for(current.step_duration()) => continue,
+                }
+            }
+            None => receiver.recv().await,
+        };
+
+        let (kind, transition_time, hold_time) = match message {
+            Some(AnimationMessage::Rotate { transition_time, hold_time }) => {
+                (AnimationKind::Rotate, transition_time, hold_time)
+            }
+            Some(AnimationMessage::Random { transition_time, hold_time }) => {
+                (AnimationKind::Random, transition_time, hold_time)
+            }
+            Some(AnimationMessage::Stop) => {
+                animation = None;
+                continue;
+            }
+            None => break,
+        };
+
+        animation = match Animation::new(&backend, kind, transition_time, hold_time).await {
+            Ok(new_animation) => Some(new_animation),
+            Err(e) => {
+                eprintln!("Failed to start animation: {}", e);
+                None
+            }
+        };
+    }
+}
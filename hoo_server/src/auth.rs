@@ -0,0 +1,164 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use warp::http::{Method, Uri};
+use warp::path::FullPath;
+use warp::{Filter, Rejection};
+
+use hoo_api::{hash_token, ApiToken, LightBackend, LightNumber, LightSelector, Permission};
+
+use crate::cache::{Freshness, LightCache};
+use crate::selectors::Aliases;
+
+/// No token, or one that doesn't match any in the config file
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// A valid token without permission for the request
+#[derive(Debug)]
+pub struct Forbidden(pub String);
+
+impl warp::reject::Reject for Forbidden {}
+
+/// For clients like `EventSource` that can't set headers
+#[derive(Debug, Default, Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+/// Lets a request through if it has a token allowed to make it. Tokens can be sent as `Authorization: Bearer <token>`,
+/// as the password of basic auth with the token's name as the user, or in an `access_token` query parameter.
+/// Without any tokens configured every request is let through.
+pub fn authorize<B: LightBackend>(
+    tokens: Vec<ApiToken>,
+    cache: LightCache<B>,
+    aliases: Aliases,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let tokens = Arc::new(tokens);
+    warp::header::optional::<String>("authorization")
+        .and(warp::query::query::<TokenQuery>().or(warp::any().map(TokenQuery::default)).unify())
+        .and(warp::method())
+        .and(warp::path::full())
+        .and_then(move |authorization: Option<String>, query: TokenQuery, method: Method, path: FullPath| {
+            let tokens = tokens.clone();
+            let cache = cache.clone();
+            let aliases = aliases.clone();
+            async move {
+                if tokens.is_empty() {
+                    return Ok(());
+                }
+
+                let token = authorization
+                    .as_deref()
+                    .and_then(credentials)
+                    .or_else(|| query.access_token.map(|secret| (None, secret)))
+                    .and_then(|(name, secret)| find_token(&tokens, name.as_deref(), &secret))
+                    .ok_or_else(|| warp::reject::custom(Unauthorized))?;
+
+                check_permission(token, &cache, &aliases, &method, path.as_str()).await
+            }
+        })
+        .untuple_one()
+}
+
+/// Only checks origins are well formed, since warp panics on ones it can't parse. Allows any origin if given none.
+pub fn cors(origins: &[String]) -> Result<warp::cors::Builder> {
+    let cors = warp::cors()
+        .allow_methods(vec!["GET", "PUT", "POST", "DELETE", "OPTIONS"])
+        .allow_headers(vec!["authorization", "content-type"]);
+    if origins.is_empty() {
+        return Ok(cors.allow_any_origin());
+    }
+
+    for origin in origins {
+        let is_origin = match Uri::from_str(origin) {
+            Ok(uri) => uri.scheme().is_some() && uri.authority().is_some() && uri.path() == "/" && !origin.ends_with('/'),
+            Err(_) => false,
+        };
+        if !is_origin {
+            bail!("Invalid CORS origin {:?}. Expected something like https://example.com:8080", origin);
+        }
+    }
+    Ok(cors.allow_origins(origins.iter().map(String::as_str)))
+}
+
+/// The user, for basic auth, and token from an authorization header
+fn credentials(authorization: &str) -> Option<(Option<String>, String)> {
+    let (scheme, value) = authorization.split_at(authorization.find(' ')?);
+    let value = value.trim();
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some((None, value.to_string()))
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let decoded = String::from_utf8(base64::decode(value).ok()?).ok()?;
+        let colon = decoded.find(':')?;
+        Some((Some(decoded[..colon].to_string()), decoded[colon + 1..].to_string()))
+    } else {
+        None
+    }
+}
+
+fn find_token<'a>(tokens: &'a [ApiToken], name: Option<&str>, secret: &str) -> Option<&'a ApiToken> {
+    let hash = hash_token(secret);
+    tokens
+        .iter()
+        .find(|token| constant_time_eq(&token.hash, &hash) && name.is_none_or(|name| name == token.name))
+}
+
+/// Compares without stopping at the first difference, so response times don't give away how much of a hash matched
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Every token can read. Tokens limited to some lights can only use the routes for single lights or selectors,
+/// since anything else, like groups, scenes and animations, can change other lights too.
+async fn check_permission<B: LightBackend>(
+    token: &ApiToken,
+    cache: &LightCache<B>,
+    aliases: &Aliases,
+    method: &Method,
+    path: &str,
+) -> Result<(), Rejection> {
+    if method == Method::GET || method == Method::HEAD {
+        return Ok(());
+    }
+    if token.permission == Permission::Read {
+        return Err(forbidden(format!("Token {} can only read", token.name)));
+    }
+    let allowed = match &token.lights {
+        Some(allowed) => allowed,
+        None => return Ok(()),
+    };
+
+    let only_own_lights = || forbidden(format!("Token {} can only control its own lights", token.name));
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').skip(1).collect();
+    let requested = match segments.as_slice() {
+        ["light", light_num, ..] => light_num.parse().ok().map(|light_num| vec![light_num]),
+        ["lights", selector, _] => selected(cache, aliases, selector).await,
+        _ => None,
+    }
+    .ok_or_else(only_own_lights)?;
+
+    let allowed = selected_by(cache, allowed).await.ok_or_else(only_own_lights)?;
+    match requested.iter().find(|light_num| !allowed.contains(light_num)) {
+        Some(light_num) => Err(forbidden(format!("Token {} can't control light {}", token.name, light_num))),
+        None => Ok(()),
+    }
+}
+
+async fn selected<B: LightBackend>(cache: &LightCache<B>, aliases: &Aliases, selector: &str) -> Option<Vec<LightNumber>> {
+    let selector: LightSelector = percent_encoding::percent_decode_str(selector).decode_utf8_lossy().parse().ok()?;
+    selected_by(cache, &selector.expand_aliases(aliases).ok()?).await
+}
+
+async fn selected_by<B: LightBackend>(cache: &LightCache<B>, selector: &LightSelector) -> Option<Vec<LightNumber>> {
+    let lights = cache.select(selector, Freshness::default()).await.ok()?;
+    Some(lights.into_keys().collect())
+}
+
+fn forbidden(message: String) -> Rejection {
+    warp::reject::custom(Forbidden(message))
+}
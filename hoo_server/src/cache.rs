@@ -0,0 +1,197 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use hoo_api::{
+    GroupCollection, HueError, Light, LightCollection, LightColorMode, LightNumber, LightSelector, LightState,
+    LightBackend, LightWatcher, StateChangeResult, WriteOutcome, WriteQueue,
+};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Freshness {
+    /// Skip the cache and read from the bridge
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    lights: LightCollection,
+    fetched: Instant,
+}
+
+/// Light states shared by every request. Reads are served from memory until the snapshot is older than the TTL,
+/// writes go through the rate limited queue and are applied to the snapshot once the backend confirms them, and the
+/// light watcher's polls keep it up to date so changes made elsewhere show up.
+#[derive(Debug, Clone)]
+pub struct LightCache<B> {
+    backend: B,
+    queue: WriteQueue,
+    snapshot: Arc<RwLock<Option<Snapshot>>>,
+    ttl: Duration,
+    transition_time: Option<u16>,
+}
+
+impl<B: LightBackend> LightCache<B> {
+    /// `transition_time` is used for writes that don't give their own
+    pub fn new(backend: B, queue: WriteQueue, ttl: Duration, transition_time: Option<u16>) -> Self {
+        Self {
+            backend,
+            queue,
+            snapshot: Arc::new(RwLock::new(None)),
+            ttl,
+            transition_time,
+        }
+    }
+
+    pub fn transition_time(&self) -> Option<u16> {
+        self.transition_time
+    }
+
+    /// Replaces the snapshot with every poll the watcher makes, so the bridge isn't polled twice. If the watcher
+    /// polls less often than the TTL, or its polls fail, reads fall back to the bridge.
+    pub fn follow(&self, watcher: &LightWatcher) {
+        let snapshot = self.snapshot.clone();
+        let mut polls = watcher.polls();
+        tokio::spawn(async move {
+            while let Some(polled) = polls.recv().await {
+                if let Some(lights) = polled {
+                    snapshot.write().unwrap().replace(Snapshot {
+                        lights,
+                        fetched: Instant::now(),
+                    });
+                }
+            }
+        });
+    }
+
+    pub async fn lights(&self, freshness: Freshness) -> Result<LightCollection, HueError> {
+        if !freshness.fresh {
+            if let Some(lights) = self.cached() {
+                return Ok(lights);
+            }
+        }
+
+        self.refresh().await
+    }
+
+    pub async fn light(&self, light_num: LightNumber, freshness: Freshness) -> Result<Light, HueError> {
+        if !freshness.fresh {
+            if let Some(light) = self.cached().and_then(|mut lights| lights.remove(&light_num)) {
+                return Ok(light);
+            }
+        }
+
+        let light = self.backend.get_light(light_num).await?;
+        if let Some(snapshot) = self.snapshot.write().unwrap().as_mut() {
+            snapshot.lights.insert(light_num, light.clone());
+        }
+        Ok(light)
+    }
+
+    /// The lights a selector matches, from the same snapshot as `lights`. Groups always come from the bridge.
+    pub async fn select(&self, selector: &LightSelector, freshness: Freshness) -> Result<LightCollection, HueError> {
+        let mut lights = self.lights(freshness).await?;
+        let groups = if selector.needs_groups() {
+            self.backend.get_all_groups().await?
+        } else {
+            GroupCollection::new()
+        };
+
+        let selected = selector.resolve(&lights, &groups);
+        lights.retain(|light_num, _| selected.contains(light_num));
+        Ok(lights)
+    }
+
+    pub async fn set_state(&self, light_num: LightNumber, state: &LightState) -> Result<WriteOutcome, HueError> {
+        let outcome = self.queue.set_state(light_num, &self.with_transition_time(state)).await?;
+        if let WriteOutcome::Sent(result) = &outcome {
+            self.record(light_num, result);
+        }
+        Ok(outcome)
+    }
+
+    /// Queues every change before waiting on any of them, so the batch goes out as fast as the queue allows
+    pub async fn set_states(&self, states: &[(LightNumber, LightState)]) -> Vec<(LightNumber, Result<WriteOutcome, HueError>)> {
+        let writes = states.iter().map(|(light_num, state)| {
+            let write = self.queue.set_state(*light_num, &self.with_transition_time(state));
+            async move { (*light_num, write.await) }
+        });
+        let results = futures::future::join_all(writes.collect::<Vec<_>>()).await;
+
+        for (light_num, outcome) in &results {
+            if let Ok(WriteOutcome::Sent(result)) = outcome {
+                self.record(*light_num, result);
+            }
+        }
+        results
+    }
+
+    /// Uses the cached on/off state instead of asking the bridge first
+    pub async fn toggle(&self, light_num: LightNumber) -> Result<WriteOutcome, HueError> {
+        let light = self.light(light_num, Freshness::default()).await?;
+        self.set_state(light_num, &LightState::new().on(!light.state.is_on())).await
+    }
+
+    /// Makes the next read go to the bridge. For writes that don't go through the cache, like group actions.
+    pub fn invalidate(&self) {
+        self.snapshot.write().unwrap().take();
+    }
+
+    fn with_transition_time(&self, state: &LightState) -> LightState {
+        let mut state = state.clone();
+        state.transitiontime = state.transitiontime.or(self.transition_time);
+        state
+    }
+
+    fn cached(&self) -> Option<LightCollection> {
+        let snapshot = self.snapshot.read().unwrap();
+        snapshot
+            .as_ref()
+            .filter(|snapshot| snapshot.fetched.elapsed() < self.ttl)
+            .map(|snapshot| snapshot.lights.clone())
+    }
+
+    async fn refresh(&self) -> Result<LightCollection, HueError> {
+        let lights = self.backend.get_all_lights().await?;
+        self.snapshot.write().unwrap().replace(Snapshot {
+            lights: lights.clone(),
+            fetched: Instant::now(),
+        });
+        Ok(lights)
+    }
+
+    /// Applies the attributes the bridge confirmed to the cached light. The bridge only confirms the size of an
+    /// increment, not where it left the light, so those make the next read go to the bridge instead.
+    fn record(&self, light_num: LightNumber, result: &StateChangeResult) {
+        let prefix = format!("/lights/{}/state/", light_num);
+        let applied: Map<String, Value> = result
+            .applied
+            .iter()
+            .filter(|change| change.address.starts_with(&prefix))
+            .map(|change| (change.attribute().to_string(), change.value.clone()))
+            .collect();
+        if applied.keys().any(|attribute| attribute.ends_with("_inc")) {
+            return self.invalidate();
+        }
+
+        let mut diff: LightState = match serde_json::from_value(Value::Object(applied)) {
+            Ok(diff) => diff,
+            Err(_) => return self.invalidate(),
+        };
+        diff.transitiontime = None;
+        diff.colormode = match (diff.hue.or(diff.sat.map(u16::from)), diff.xy, diff.ct) {
+            (_, Some(_), _) => Some(LightColorMode::XY),
+            (_, _, Some(_)) => Some(LightColorMode::CT),
+            (Some(_), _, _) => Some(LightColorMode::HS),
+            _ => None,
+        };
+
+        let mut snapshot = self.snapshot.write().unwrap();
+        if let Some(light) = snapshot.as_mut().and_then(|snapshot| snapshot.lights.get_mut(&light_num)) {
+            light.state = LightState::combine(&light.state, &diff);
+        }
+    }
+}
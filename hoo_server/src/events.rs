@@ -0,0 +1,41 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::stream::StreamExt;
+use warp::Filter;
+
+use hoo_api::LightWatcher;
+
+/// `GET /events` streams light changes as server-sent events. Each connection starts with a `lights` event
+/// holding every light, followed by a `light` event per change. All connections share one watcher, so the
+/// bridge is polled at the same rate no matter how many clients are listening.
+pub fn routes(watcher: Arc<LightWatcher>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("events")
+        .and(warp::get())
+        .and_then(move || light_events(watcher.clone()))
+}
+
+async fn light_events(watcher: Arc<LightWatcher>) -> Result<impl warp::Reply, Infallible> {
+    // Subscribe before taking the snapshot so nothing falls between the two
+    let receiver = watcher.subscribe();
+    let snapshot = watcher.latest().map(|lights| event("lights", to_json(&lights)));
+
+    let changes = receiver.filter_map(move |received| match received {
+        Ok(change) => Some(event("light", to_json(&change))),
+        // The client fell behind, so bring it back in sync with a fresh snapshot
+        Err(_) => watcher.latest().map(|lights| event("lights", to_json(&lights))),
+    });
+
+    let events = tokio::stream::iter(snapshot).chain(changes).map(Ok::<_, Infallible>);
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+}
+
+fn event(name: &'static str, data: Value) -> impl warp::sse::ServerSentEvent {
+    (warp::sse::event(name), warp::sse::json(data))
+}
+
+fn to_json<T: Serialize>(data: &T) -> Value {
+    serde_json::to_value(data).unwrap_or(Value::Null)
+}
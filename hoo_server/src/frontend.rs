@@ -0,0 +1,57 @@
+use rust_embed::RustEmbed;
+use warp::http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use warp::http::Response;
+use warp::hyper::Body;
+use warp::path::Tail;
+use warp::{Filter, Rejection};
+
+/// The production build of `hoo_frontend`. Release builds embed it in the binary, debug builds read it from disk so
+/// it can be rebuilt without restarting the server. Either way the build folder has to exist when the server is
+/// compiled, so `npm run build` has to have been run first.
+#[derive(RustEmbed)]
+#[folder = "../hoo_frontend/build/"]
+struct Assets;
+
+/// Create React App puts a content hash in the name of everything under `static/`, so those never change
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// Everything else, `index.html` especially, has to be checked for changes so a new build gets picked up
+const REVALIDATE: &str = "no-cache";
+
+/// Serves the frontend for any GET outside of `/api`. Paths that don't look like files get `index.html`, so the app
+/// can do its own routing.
+pub fn routes() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path::tail())
+        .and_then(|tail: Tail| async move { serve(tail.as_str()) })
+}
+
+fn serve(path: &str) -> Result<Response<Body>, Rejection> {
+    // Unknown API routes should get the API's JSON 404, not the app
+    if path == "api" || path.starts_with("api/") {
+        return Err(warp::reject::not_found());
+    }
+
+    let path = if path.is_empty() { "index.html" } else { path };
+    if let Some(contents) = Assets::get(path) {
+        return Ok(asset(path, contents.into()));
+    }
+
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match Assets::get("index.html") {
+        Some(contents) if !file_name.contains('.') => Ok(asset("index.html", contents.into())),
+        _ => Err(warp::reject::not_found()),
+    }
+}
+
+fn asset(path: &str, body: Body) -> Response<Body> {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let cache = if path.starts_with("static/") { IMMUTABLE } else { REVALIDATE };
+
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    if let Ok(mime) = HeaderValue::from_str(mime.as_ref()) {
+        headers.insert(CONTENT_TYPE, mime);
+    }
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache));
+    response
+}
@@ -0,0 +1,134 @@
+use std::convert::{Infallible, TryFrom};
+
+use warp::Filter;
+
+use hoo_api::{GroupAction, GroupAttributes, GroupNumber, LightBackend, LightState};
+use hoo_api_types::LightStateQuery;
+
+use crate::cache::LightCache;
+use crate::reply::{self, Created};
+
+pub fn routes<B: LightBackend>(backend: B, cache: LightCache<B>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let backend_clone = backend.clone();
+    let all_groups = warp::path!("groups")
+        .and(warp::get())
+        .and_then(move || get_all_groups(backend_clone.clone()));
+
+    let backend_clone = backend.clone();
+    let get_group = warp::path!("group" / u8)
+        .and(warp::get())
+        .and_then(move |group_num| get_group(backend_clone.clone(), group_num));
+
+    let backend_clone = backend.clone();
+    let create_group = warp::path!("groups")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(move |attributes| create_group(backend_clone.clone(), attributes));
+
+    let backend_clone = backend.clone();
+    let update_group = warp::path!("group" / u8)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and_then(move |group_num, attributes| update_group(backend_clone.clone(), group_num, attributes));
+
+    let backend_clone = backend.clone();
+    let delete_group = warp::path!("group" / u8)
+        .and(warp::delete())
+        .and_then(move |group_num| delete_group(backend_clone.clone(), group_num));
+
+    let backend_clone = backend.clone();
+    let cache_clone = cache.clone();
+    let group_on = warp::path!("group" / u8 / "on")
+        .and(warp::put())
+        .and_then(move |group_num| set_action(backend_clone.clone(), cache_clone.clone(), group_num, LightState::new().on(true).into()));
+
+    let backend_clone = backend.clone();
+    let cache_clone = cache.clone();
+    let group_off = warp::path!("group" / u8 / "off")
+        .and(warp::put())
+        .and_then(move |group_num| set_action(backend_clone.clone(), cache_clone.clone(), group_num, LightState::new().on(false).into()));
+
+    let backend_clone = backend;
+    let cache_clone = cache;
+    let group_state = warp::path!("group" / u8 / "state")
+        .and(warp::put())
+        .and(warp::query::query())
+        .and_then(move |group_num, query: LightStateQuery| {
+            set_action_from_query(backend_clone.clone(), cache_clone.clone(), group_num, query)
+        });
+
+    let put_group = group_on
+        .or(group_off)
+        .or(group_state);
+
+    all_groups
+        .or(get_group)
+        .or(create_group)
+        .or(put_group)
+        .or(update_group)
+        .or(delete_group)
+}
+
+async fn get_all_groups<B: LightBackend>(backend: B) -> Result<impl warp::Reply, Infallible> {
+    match backend.get_all_groups().await {
+        Ok(groups) => Ok(reply::ok(&groups)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn get_group<B: LightBackend>(backend: B, group_num: GroupNumber) -> Result<impl warp::Reply, Infallible> {
+    match backend.get_group(group_num).await {
+        Ok(group) => Ok(reply::ok(&group)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn create_group<B: LightBackend>(backend: B, attributes: GroupAttributes) -> Result<impl warp::Reply, Infallible> {
+    match backend.create_group(&attributes).await {
+        Ok(group_num) => Ok(reply::created(&Created { id: group_num })),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn update_group<B: LightBackend>(backend: B, group_num: GroupNumber, attributes: GroupAttributes) -> Result<impl warp::Reply, Infallible> {
+    match backend.update_group(group_num, &attributes).await {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn delete_group<B: LightBackend>(backend: B, group_num: GroupNumber) -> Result<impl warp::Reply, Infallible> {
+    match backend.delete_group(group_num).await {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn set_action_from_query<B: LightBackend>(
+    backend: B,
+    cache: LightCache<B>,
+    group_num: GroupNumber,
+    query: LightStateQuery,
+) -> Result<impl warp::Reply, Infallible> {
+    let state = match LightState::try_from(query).and_then(|state| state.validate().map(|_| state)) {
+        Ok(state) => state,
+        Err(e) => return Ok(reply::error(e)),
+    };
+    set_action(backend, cache, group_num, state.into()).await
+}
+
+async fn set_action<B: LightBackend>(
+    backend: B,
+    cache: LightCache<B>,
+    group_num: GroupNumber,
+    mut action: GroupAction,
+) -> Result<reply::Reply, Infallible> {
+    action.state.transitiontime = action.state.transitiontime.or(cache.transition_time());
+    let result = backend.set_group_action(group_num, &action).await;
+    cache.invalidate();
+
+    match result {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
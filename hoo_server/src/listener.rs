@@ -0,0 +1,138 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use rustls::internal::pemfile;
+use rustls::{NoClientAuth, ServerConfig};
+use warp::Filter;
+
+use hoo_api::Profile;
+
+use crate::options::Options;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8000";
+
+/// Where the server accepts connections
+#[derive(Debug, Clone)]
+pub enum Listener {
+    Http(SocketAddr),
+    /// With the PEM certificate chain and private key, already checked to be usable
+    Https { addr: SocketAddr, cert: Vec<u8>, key: Vec<u8> },
+    Unix(PathBuf),
+}
+
+impl Listener {
+    /// Settings from the command line or environment win over the profile's. A socket path wins over an address.
+    pub fn new(options: &Options, profile: &Profile) -> Result<Self> {
+        let cert = options.tls_cert.as_ref().or(profile.tls_cert.as_ref());
+        let key = options.tls_key.as_ref().or(profile.tls_key.as_ref());
+
+        if let Some(socket) = options.socket.as_ref().or(profile.socket.as_ref()) {
+            if cert.is_some() || key.is_some() {
+                bail!(
+                    "Can't serve TLS on the Unix socket {}. Leave out either the socket or the TLS certificate and key",
+                    socket.display()
+                );
+            }
+            return Ok(Listener::Unix(socket.clone()));
+        }
+
+        let mut addr = profile.bind.unwrap_or_else(|| DEFAULT_ADDRESS.parse().unwrap());
+        if let Some(address) = options.address {
+            addr.set_ip(address);
+        }
+        if let Some(port) = options.port {
+            addr.set_port(port);
+        }
+
+        match (cert, key) {
+            (Some(cert), Some(key)) => {
+                let (cert, key) = read_tls_files(cert, key)?;
+                Ok(Listener::Https { addr, cert, key })
+            }
+            (None, None) => Ok(Listener::Http(addr)),
+            _ => bail!("A TLS certificate and key must be given together"),
+        }
+    }
+
+    /// Whether only this machine can connect
+    pub fn is_local(&self) -> bool {
+        match self {
+            Listener::Http(addr) | Listener::Https { addr, .. } => addr.ip().is_loopback(),
+            Listener::Unix(_) => true,
+        }
+    }
+
+    pub async fn serve<F>(self, routes: F) -> Result<()>
+    where
+        F: Filter<Error = warp::Rejection> + Clone + Send + Sync + 'static,
+        F::Extract: warp::Reply,
+    {
+        match self {
+            Listener::Http(addr) => {
+                println!("Hoo server listening on http://{}", addr);
+                warp::serve(routes).run(addr).await;
+            }
+            Listener::Https { addr, cert, key } => {
+                println!("Hoo server listening on https://{}", addr);
+                warp::serve(routes).tls().cert(cert).key(key).run(addr).await;
+            }
+            Listener::Unix(path) => serve_unix(routes, path).await?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a PEM certificate chain and private key, and checks rustls can use them. warp panics on ones it can't, so
+/// this is the last chance to fail nicely.
+fn read_tls_files(cert_path: &Path, key_path: &Path) -> Result<(Vec<u8>, Vec<u8>)> {
+    let read = |path: &Path| std::fs::read(path).map_err(|e| anyhow!("Couldn't read TLS file {}: {}", path.display(), e));
+    let (cert, key) = (read(cert_path)?, read(key_path)?);
+
+    let certs = pemfile::certs(&mut cert.as_slice())
+        .ok()
+        .filter(|certs| !certs.is_empty())
+        .ok_or_else(|| anyhow!("No PEM certificates in {}", cert_path.display()))?;
+    // The same key formats warp takes
+    let private_key = pemfile::pkcs8_private_keys(&mut key.as_slice())
+        .ok()
+        .filter(|keys| !keys.is_empty())
+        .or_else(|| pemfile::rsa_private_keys(&mut key.as_slice()).ok())
+        .and_then(|mut keys| keys.drain(..).next())
+        .ok_or_else(|| anyhow!("No PKCS#8 or RSA private key in {}", key_path.display()))?;
+
+    ServerConfig::new(NoClientAuth::new())
+        .set_single_cert(certs, private_key)
+        .map_err(|e| anyhow!("Can't use TLS key {}: {}", key_path.display(), e))?;
+
+    Ok((cert, key))
+}
+
+#[cfg(unix)]
+async fn serve_unix<F>(routes: F, path: PathBuf) -> Result<()>
+where
+    F: Filter<Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+{
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by a server that didn't shut down cleanly would stop the bind
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            bail!("{} already exists and isn't a socket", path.display());
+        }
+        std::fs::remove_file(&path)?;
+    }
+
+    let mut listener = tokio::net::UnixListener::bind(&path)?;
+    println!("Hoo server listening on unix:{}", path.display());
+    warp::serve(routes).run_incoming(listener.incoming()).await;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn serve_unix<F>(_routes: F, _path: PathBuf) -> Result<()> {
+    bail!("Unix domain sockets aren't supported on this platform")
+}
@@ -1,111 +1,340 @@
+mod animation;
+mod auth;
+mod cache;
+mod events;
+#[cfg(feature = "frontend")]
+mod frontend;
+mod groups;
+mod listener;
 mod options;
+mod reply;
+mod rules;
+mod scenes;
+mod scheduler;
+mod schedules;
+mod selectors;
+mod sun;
+
+use std::collections::BTreeMap;
+use std::convert::{Infallible, TryFrom};
+use std::sync::Arc;
+use std::time::Duration;
 
-use std::convert::Infallible;
 use anyhow::Result;
+use serde::Serialize;
 use structopt::StructOpt;
+use warp::http::StatusCode;
 use warp::Filter;
 
-use hoo_api::HueClient;
+use hoo_api::{
+    write_queue, BridgeTrust, Config, HueClient, HueError, LightBackend, LightState, SceneStore,
+    WriteOutcome, WriteQueue,
+};
 use hoo_api_types::LightStateQuery;
 
+use animation::{AnimationMessage, AnimationSender};
+use cache::{Freshness, LightCache};
+use listener::Listener;
+use reply::ApiError;
+use scheduler::{Location, Scheduler};
+
+/// Passes requests that do or don't say they're sending JSON, so a JSON body and a query string can share a path
+fn json_content(expected: bool) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and_then(move |content_type: Option<String>| async move {
+            let is_json = content_type.is_some_and(|content_type| content_type.starts_with("application/json"));
+            if is_json == expected {
+                Ok(())
+            } else {
+                Err(warp::reject())
+            }
+        })
+        .untuple_one()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    dotenv::dotenv()?;
+    dotenv::dotenv().ok();
     let options = options::Options::from_args();
 
-    let addr: std::net::SocketAddr = "127.0.0.1:8000".parse().unwrap();
+    let config = Config::load(options.config.as_deref())?;
+    let profile = config.profile(options.profile.as_deref())?;
+
+    let listener = Listener::new(&options, &profile)?;
+
+    let env = options.hue_base_uri.zip(options.hue_user_id);
+    let bridge = profile.credentials(env, options.profile.is_some())?;
+
+    let policy = profile.request_policy(options.bridge_timeout, options.bridge_retries);
+    let client = HueClient::with_trust(&bridge.base_uri, &bridge.user_id, BridgeTrust::for_bridge(&bridge))
+        .with_policy(policy);
 
-    let client = HueClient::new(&options.hue_base_uri, &options.hue_user_id);
+    let queue = WriteQueue::spawn(client.clone(), options.commands_per_second, write_queue::DEFAULT_MAX_PENDING);
+    let cache_ttl = Duration::from_millis(options.cache_ttl.get());
+    let cache = LightCache::new(client.clone(), queue.clone(), cache_ttl, profile.transition_time);
+    let watcher = Arc::new(client.watch_lights(Duration::from_millis(options.poll_interval.get())));
+    cache.follow(&watcher);
 
-    let client_clone = client.clone();
-    let all_lights = warp::get()
-        .and(warp::path("lights")
-        .and_then(move || get_all_lights(client_clone.clone())));
-    
-    let client_clone = client.clone();
-    let get_light = warp::get()
-        .and(warp::path!("light" / u8))
-        .and_then(move |light_num| get_light(client_clone.clone(), light_num));
+    let cache_clone = cache.clone();
+    let all_lights = warp::path!("lights")
+        .and(warp::get())
+        .and(warp::query::query())
+        .and_then(move |freshness| get_all_lights(cache_clone.clone(), freshness));
+
+    let cache_clone = cache.clone();
+    let get_light = warp::path!("light" / u8)
+        .and(warp::get())
+        .and(warp::query::query())
+        .and_then(move |light_num, freshness| get_light(cache_clone.clone(), light_num, freshness));
+
+    let cache_clone = cache.clone();
+    let all_lights_state = warp::path!("lights" / "state")
+        .and(warp::put())
+        .and(warp::body::json())
+        .and_then(move |states| set_states(cache_clone.clone(), states));
 
-    let client_clone = client.clone();
+    let cache_clone = cache.clone();
     let light_on = warp::path!("light" / u8 / "on")
-        .and_then(move |light_num| on(client_clone.clone(), light_num));
+        .and(warp::put())
+        .and_then(move |light_num| on(cache_clone.clone(), light_num));
 
-    let client_clone = client.clone();
+    let cache_clone = cache.clone();
     let light_off = warp::path!("light" / u8 / "off")
-        .and_then(move |light_num| off(client_clone.clone(), light_num));
+        .and(warp::put())
+        .and_then(move |light_num| off(cache_clone.clone(), light_num));
 
-    let client_clone = client.clone();
+    let cache_clone = cache.clone();
     let light_toggle = warp::path!("light" / u8 / "toggle")
-        .and_then(move |light_num| toggle(client_clone.clone(), light_num));
-    
-    let client_clone = client.clone();
-    let light_state = warp::path!("light" / u8 / "state")
+        .and(warp::put())
+        .and_then(move |light_num| toggle(cache_clone.clone(), light_num));
+
+    let cache_clone = cache.clone();
+    let light_state_body = warp::path!("light" / u8 / "state")
+        .and(warp::put())
+        .and(json_content(true))
+        .and(warp::body::json())
+        .and_then(move |light_num, state| set_state(cache_clone.clone(), light_num, state));
+
+    let cache_clone = cache.clone();
+    let light_state_query = warp::path!("light" / u8 / "state")
+        .and(warp::put())
+        .and(json_content(false))
         .and(warp::query::query())
-        .and_then(move |light_num, state| set_state(client_clone.clone(), light_num, state));
+        .and_then(move |light_num, query| set_state_from_query(cache_clone.clone(), light_num, query));
+
+    let queue_clone = queue.clone();
+    let queue_stats = warp::path!("queue")
+        .and(warp::get())
+        .and_then(move || get_queue_stats(queue_clone.clone()));
 
-    let put_light = warp::put().and(
-        light_on
+    let aliases = Arc::new(profile.aliases);
+    let selected_lights = selectors::routes(cache.clone(), aliases.clone());
+
+    let put_light = all_lights_state
+        .or(light_on)
         .or(light_off)
         .or(light_toggle)
-        .or(light_state)
-    );
+        .or(light_state_body)
+        .or(light_state_query);
+
+    let events = events::routes(watcher);
+
+    let groups = groups::routes(client.clone(), cache.clone());
+    let rules = rules::routes(client.clone());
+    let scenes = scenes::bridge_routes(client.clone(), cache.clone())
+        .or(scenes::routes(client.clone(), SceneStore::new(&options.scene_file), cache.clone()));
+
+    let location = match (options.latitude, options.longitude) {
+        (Some(latitude), Some(longitude)) => Some(Location { latitude, longitude }),
+        _ => None,
+    };
+    let scheduler = Scheduler::load(&options.schedule_file, location)?;
+    scheduler.spawn(cache.clone());
+    let schedules = schedules::routes(client.clone(), scheduler);
+
+    let animation_sender = animation::spawn(client.clone(), queue);
+
+    let sender_clone = animation_sender.clone();
+    let rotate = warp::path!("rotate" / u16 / u16)
+        .and(warp::put())
+        .and_then(move |transition_time, hold_time| rotate(sender_clone.clone(), transition_time, hold_time));
+
+    let sender_clone = animation_sender.clone();
+    let random = warp::path!("random" / u16 / u16)
+        .and(warp::put())
+        .and_then(move |transition_time, hold_time| random(sender_clone.clone(), transition_time, hold_time));
 
-    let cors = warp::cors().allow_any_origin().allow_methods(vec!["GET", "PUT", "OPTIONS"]);
+    let sender_clone = animation_sender.clone();
+    let stop = warp::path!("stop")
+        .and(warp::put())
+        .and_then(move || stop(sender_clone.clone()));
 
-    let routes = warp::path("api")
+    let animations = rotate
+        .or(random)
+        .or(stop);
+
+    let cors_origins = if options.cors_origins.is_empty() {
+        &profile.cors_origins
+    } else {
+        &options.cors_origins
+    };
+    let cors = auth::cors(cors_origins)?;
+
+    if profile.tokens.is_empty() && !listener.is_local() {
+        eprintln!("Warning: no API tokens are configured, so anyone who can reach the server can control the lights");
+    }
+    let authorized = auth::authorize(profile.tokens, cache, aliases);
+
+    let api = warp::path("api")
+        .and(authorized)
         .and(
             all_lights
             .or(get_light)
             .or(put_light)
-        )
+            .or(selected_lights)
+            .or(queue_stats)
+            .or(events)
+            .or(groups)
+            .or(rules)
+            .or(scenes)
+            .or(schedules)
+            .or(animations)
+        );
+
+    #[cfg(feature = "frontend")]
+    let routes = api.or(frontend::routes());
+    #[cfg(not(feature = "frontend"))]
+    let routes = api;
+
+    let routes = routes
+        .recover(reply::handle_rejection)
         .with(cors);
-    
-    println!("Hoo server listening on http://{}", addr);
-    warp::serve(routes).run(addr).await;
 
-    Ok(())
+    listener.serve(routes).await
 }
 
 
-async fn get_all_lights(client: HueClient) -> Result<impl warp::Reply, Infallible> {
-    match client.get_all_lights().await {
-        Ok(lights) => Ok(warp::reply::json(&lights)),
-        Err(e) => Ok(warp::reply::json(&format!("{}", e))),
+async fn get_all_lights<B: LightBackend>(cache: LightCache<B>, freshness: Freshness) -> Result<impl warp::Reply, Infallible> {
+    match cache.lights(freshness).await {
+        Ok(lights) => Ok(reply::ok(&lights)),
+        Err(e) => Ok(reply::error(e)),
     }
 }
 
-async fn get_light(client: HueClient, light_num: u8) -> Result<impl warp::Reply, Infallible> {
-    match client.get_light(light_num).await {
-        Ok(light) => Ok(warp::reply::json(&light)),
-        Err(e) => Ok(warp::reply::json(&format!("{}", e))),
+async fn get_light<B: LightBackend>(cache: LightCache<B>, light_num: u8, freshness: Freshness) -> Result<impl warp::Reply, Infallible> {
+    match cache.light(light_num, freshness).await {
+        Ok(light) => Ok(reply::ok(&light)),
+        Err(e) => Ok(reply::error(e)),
     }
 }
 
-async fn on(client: HueClient, light_num: u8) -> Result<impl warp::Reply, Infallible> {
-    match client.on(light_num).await {
-        Ok(_) => Ok(warp::reply::json(&format!("Light {} turned on", light_num))),
-        Err(e) => Ok(warp::reply::json(&format!("{}", e))),
+async fn on<B: LightBackend>(cache: LightCache<B>, light_num: u8) -> Result<impl warp::Reply, Infallible> {
+    Ok(write_reply(light_num, cache.set_state(light_num, &LightState::new().on(true)).await))
+}
+
+async fn off<B: LightBackend>(cache: LightCache<B>, light_num: u8) -> Result<impl warp::Reply, Infallible> {
+    Ok(write_reply(light_num, cache.set_state(light_num, &LightState::new().on(false)).await))
+}
+
+async fn toggle<B: LightBackend>(cache: LightCache<B>, light_num: u8) -> Result<impl warp::Reply, Infallible> {
+    Ok(write_reply(light_num, cache.toggle(light_num).await))
+}
+
+async fn set_state<B: LightBackend>(cache: LightCache<B>, light_num: u8, state: LightState) -> Result<reply::Reply, Infallible> {
+    if let Err(e) = state.validate() {
+        return Ok(reply::error(e));
+    }
+    Ok(write_reply(light_num, cache.set_state(light_num, &state).await))
+}
+
+async fn set_state_from_query<B: LightBackend>(cache: LightCache<B>, light_num: u8, query: LightStateQuery) -> Result<impl warp::Reply, Infallible> {
+    match LightState::try_from(query) {
+        Ok(state) => set_state(cache, light_num, state).await,
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+/// Applies every state or, if any of them is invalid, none of them. Responds with 207 if some lights failed.
+async fn set_states<B: LightBackend>(cache: LightCache<B>, states: BTreeMap<u8, LightState>) -> Result<impl warp::Reply, Infallible> {
+    for (light_num, state) in &states {
+        if let Err(e) = state.validate() {
+            return Ok(reply::error(ApiError::bad_request(&format!("Light {}: {}", light_num, e))));
+        }
+    }
+
+    let states: Vec<_> = states.into_iter().collect();
+    Ok(batch_reply(&cache, &states).await)
+}
+
+/// Sets each light and replies with a result per light, with 207 if some of them failed
+async fn batch_reply<B: LightBackend>(cache: &LightCache<B>, states: &[(u8, LightState)]) -> reply::Reply {
+    let results: BTreeMap<u8, LightResult> = cache
+        .set_states(states)
+        .await
+        .into_iter()
+        .map(|(light_num, outcome)| match write_result(light_num, outcome) {
+            Ok(outcome) => (light_num, LightResult::Done(outcome)),
+            Err(e) => (light_num, LightResult::Failed(e)),
+        })
+        .collect();
+
+    let status = if results.values().all(|result| matches!(result, LightResult::Done(_))) {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    reply::with_status(&results, status)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum LightResult {
+    Done(WriteOutcome),
+    Failed(ApiError),
+}
+
+fn write_reply(light_num: u8, outcome: Result<WriteOutcome, HueError>) -> reply::Reply {
+    match write_result(light_num, outcome) {
+        Ok(outcome) => reply::ok(&outcome),
+        Err(e) => reply::error(e),
+    }
+}
+
+fn write_result(light_num: u8, outcome: Result<WriteOutcome, HueError>) -> std::result::Result<WriteOutcome, ApiError> {
+    match outcome {
+        Ok(WriteOutcome::Dropped) => Err(ApiError::queue_full(&format!(
+            "Too many pending writes, dropped the change to light {}",
+            light_num
+        ))),
+        Ok(outcome) => Ok(outcome),
+        Err(e) => Err(e.into()),
     }
 }
 
-async fn off(client: HueClient, light_num: u8) -> Result<impl warp::Reply, Infallible> {
-    match client.off(light_num).await {
-        Ok(_) => Ok(warp::reply::json(&format!("Light {} turned off", light_num))),
-        Err(e) => Ok(warp::reply::json(&format!("{}", e))),
+async fn get_queue_stats(queue: WriteQueue) -> Result<impl warp::Reply, Infallible> {
+    Ok(reply::ok(&queue.stats()))
+}
+
+async fn rotate(sender: AnimationSender, transition_time: u16, hold_time: u16) -> Result<impl warp::Reply, Infallible> {
+    let message = AnimationMessage::Rotate { transition_time, hold_time };
+    match sender.send(message) {
+        Ok(_) => Ok(reply::ok(&message)),
+        Err(e) => Ok(reply::error(ApiError::internal(&e.to_string()))),
     }
 }
 
-async fn toggle(client: HueClient, light_num: u8) -> Result<impl warp::Reply, Infallible> {
-    match client.toggle(light_num).await {
-        Ok(_) => Ok(warp::reply::json(&format!("Light {} toggled", light_num))),
-        Err(e) => Ok(warp::reply::json(&format!("{}", e))),
+async fn random(sender: AnimationSender, transition_time: u16, hold_time: u16) -> Result<impl warp::Reply, Infallible> {
+    let message = AnimationMessage::Random { transition_time, hold_time };
+    match sender.send(message) {
+        Ok(_) => Ok(reply::ok(&message)),
+        Err(e) => Ok(reply::error(ApiError::internal(&e.to_string()))),
     }
 }
 
-async fn set_state(client: HueClient, light_num: u8, state: LightStateQuery) -> Result<impl warp::Reply, Infallible> {
-    match client.set_state(light_num, &state.into()).await {
-        Ok(_) => Ok(warp::reply::json(&format!("Light {} state set to\n{:?}", light_num, &state))),
-        Err(e) => Ok(warp::reply::json(&format!("{}", e))),
+async fn stop(sender: AnimationSender) -> Result<impl warp::Reply, Infallible> {
+    let message = AnimationMessage::Stop;
+    match sender.send(message) {
+        Ok(_) => Ok(reply::ok(&message)),
+        Err(e) => Ok(reply::error(ApiError::internal(&e.to_string()))),
     }
 }
@@ -1,9 +1,65 @@
+use std::net::IpAddr;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 pub struct Options {
     #[structopt(env, hide_env_values = true)]
-    pub hue_base_uri: String,
+    pub hue_base_uri: Option<String>,
     #[structopt(env, hide_env_values = true)]
-    pub hue_user_id: String,
+    pub hue_user_id: Option<String>,
+    /// Config file to read instead of hoo/config.toml in the user config directory
+    #[structopt(long, env = "HOO_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Config profile to use instead of the default one. Its bridge takes precedence over HUE_BASE_URI and HUE_USER_ID.
+    #[structopt(long, env = "HOO_PROFILE")]
+    pub profile: Option<String>,
+    /// Milliseconds to wait for the bridge to answer. Defaults to the profile's bridge_timeout, or 5000.
+    #[structopt(long, env = "HOO_BRIDGE_TIMEOUT")]
+    pub bridge_timeout: Option<u64>,
+    /// Times to retry reads the bridge doesn't answer. Defaults to the profile's bridge_retries, or 2.
+    #[structopt(long, env = "HOO_BRIDGE_RETRIES")]
+    pub bridge_retries: Option<u32>,
+    /// Address to listen on. Defaults to the profile's bind address, or 127.0.0.1.
+    #[structopt(long, env = "HOO_ADDRESS")]
+    pub address: Option<IpAddr>,
+    /// Port to listen on. Defaults to the profile's bind address, or 8000.
+    #[structopt(long, env = "HOO_PORT")]
+    pub port: Option<u16>,
+    /// PEM certificate to serve HTTPS with. Needs --tls-key as well.
+    #[structopt(long, env = "HOO_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for --tls-cert, either PKCS#8 or RSA
+    #[structopt(long, env = "HOO_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+    /// Listen on a Unix domain socket at this path instead of a TCP address
+    #[structopt(long, env = "HOO_SOCKET")]
+    pub socket: Option<PathBuf>,
+    /// Browser origins allowed to call the API, e.g. https://hoo.example.com. Any origin if none are given here or in
+    /// the profile.
+    #[structopt(long = "cors-origin", env = "HOO_CORS_ORIGINS", use_delimiter = true)]
+    pub cors_origins: Vec<String>,
+    /// Where saved scenes are kept
+    #[structopt(long, env = "HOO_SCENE_FILE", default_value = "scenes.json")]
+    pub scene_file: PathBuf,
+    /// Where scheduled jobs are kept
+    #[structopt(long, env = "HOO_SCHEDULE_FILE", default_value = "schedules.json")]
+    pub schedule_file: PathBuf,
+    /// Used to work out sunrise and sunset times
+    #[structopt(long, env = "HOO_LATITUDE", allow_hyphen_values = true)]
+    pub latitude: Option<f64>,
+    /// Positive east of Greenwich
+    #[structopt(long, env = "HOO_LONGITUDE", allow_hyphen_values = true)]
+    pub longitude: Option<f64>,
+    /// Milliseconds between polls of the bridge for changes pushed to `/api/events` and kept in the light cache
+    #[structopt(long, env = "HOO_POLL_INTERVAL", default_value = "1000")]
+    pub poll_interval: NonZeroU64,
+    /// Milliseconds light states are served from memory before being read from the bridge again
+    #[structopt(long, env = "HOO_CACHE_TTL", default_value = "2000")]
+    pub cache_ttl: NonZeroU64,
+    /// Light commands sent to the bridge per second. Changes to the same light made in between are merged.
+    #[structopt(long, env = "HOO_COMMANDS_PER_SECOND", default_value = "10")]
+    pub commands_per_second: u32,
 }
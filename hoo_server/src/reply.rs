@@ -0,0 +1,209 @@
+use std::convert::Infallible;
+
+use serde::Serialize;
+use warp::http::StatusCode;
+use warp::reply::{Json, WithStatus};
+use warp::Rejection;
+
+use hoo_api::{BridgeError, BridgeErrorKind, HueError, LightStateError, SelectorError};
+
+use crate::auth::{Forbidden, Unauthorized};
+
+pub type Reply = WithStatus<Json>;
+
+pub fn ok<T: Serialize>(value: &T) -> Reply {
+    warp::reply::with_status(warp::reply::json(value), StatusCode::OK)
+}
+
+pub fn created<T: Serialize>(value: &T) -> Reply {
+    warp::reply::with_status(warp::reply::json(value), StatusCode::CREATED)
+}
+
+pub fn with_status<T: Serialize>(value: &T, status: StatusCode) -> Reply {
+    warp::reply::with_status(warp::reply::json(value), status)
+}
+
+pub fn error<E: Into<ApiError>>(error: E) -> Reply {
+    let error = error.into();
+    warp::reply::with_status(warp::reply::json(&error), error.status)
+}
+
+/// The body for newly created resources the bridge only returns an id for
+#[derive(Debug, Clone, Serialize)]
+pub struct Created<T> {
+    pub id: T,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Deleted<T> {
+    pub deleted: T,
+}
+
+/// The body of every error response, e.g.
+/// `{"code":"bridge_error","message":"resource, /lights/9, not available (type 3 at /lights/9)","bridge":{...}}`
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    code: ErrorCode,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bridge: Option<BridgeError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    UnsupportedMediaType,
+    PayloadTooLarge,
+    QueueFull,
+    /// The bridge answered with an error
+    BridgeError,
+    /// The bridge couldn't be reached or sent something unreadable
+    BridgeUnavailable,
+    /// The bridge didn't answer in time
+    BridgeTimeout,
+    /// The bridge's certificate wasn't the pinned one, or was for another bridge
+    UntrustedBridge,
+    Internal,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: ErrorCode, message: &str) -> Self {
+        Self {
+            status,
+            code,
+            message: message.to_string(),
+            bridge: None,
+        }
+    }
+
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::BadRequest, message)
+    }
+
+    pub fn not_found(message: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, message)
+    }
+
+    pub fn internal(message: &str) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal, message)
+    }
+
+    pub fn queue_full(message: &str) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::QueueFull, message)
+    }
+}
+
+impl From<HueError> for ApiError {
+    fn from(error: HueError) -> Self {
+        let message = error.to_string();
+        match error {
+            HueError::Bridge(bridge_error) => ApiError {
+                status: bridge_status(bridge_error.kind),
+                code: ErrorCode::BridgeError,
+                message,
+                bridge: Some(bridge_error),
+            },
+            HueError::InvalidRule(_) => ApiError::bad_request(&message),
+            HueError::Certificate(_) => ApiError::new(StatusCode::BAD_GATEWAY, ErrorCode::UntrustedBridge, &message),
+            HueError::Timeout(_) => ApiError::new(StatusCode::GATEWAY_TIMEOUT, ErrorCode::BridgeTimeout, &message),
+            HueError::CircuitOpen { .. } => {
+                ApiError::new(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::BridgeUnavailable, &message)
+            }
+            HueError::Network(_)
+            | HueError::ServerError(_)
+            | HueError::Json(_)
+            | HueError::Utf8(_)
+            | HueError::UnexpectedResponse(_) => {
+                ApiError::new(StatusCode::BAD_GATEWAY, ErrorCode::BridgeUnavailable, &message)
+            }
+            HueError::InvalidUri(_) | HueError::InvalidRequest(_) | HueError::Io(_) => ApiError::internal(&message),
+        }
+    }
+}
+
+impl From<LightStateError> for ApiError {
+    fn from(error: LightStateError) -> Self {
+        ApiError::bad_request(&error.to_string())
+    }
+}
+
+impl From<SelectorError> for ApiError {
+    fn from(error: SelectorError) -> Self {
+        ApiError::bad_request(&error.to_string())
+    }
+}
+
+/// Scheduler errors are mostly invalid jobs. Failing to write the jobs file is the server's problem.
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        if error.downcast_ref::<std::io::Error>().is_some() {
+            ApiError::internal(&error.to_string())
+        } else {
+            ApiError::bad_request(&error.to_string())
+        }
+    }
+}
+
+fn bridge_status(kind: BridgeErrorKind) -> StatusCode {
+    match kind {
+        BridgeErrorKind::ResourceNotAvailable => StatusCode::NOT_FOUND,
+        BridgeErrorKind::InvalidJson
+        | BridgeErrorKind::MissingParameters
+        | BridgeErrorKind::ParameterNotAvailable
+        | BridgeErrorKind::InvalidParameterValue
+        | BridgeErrorKind::ParameterNotModifiable
+        | BridgeErrorKind::DeviceIsOff => StatusCode::BAD_REQUEST,
+        BridgeErrorKind::MethodNotAvailable => StatusCode::METHOD_NOT_ALLOWED,
+        BridgeErrorKind::TooManyItems
+        | BridgeErrorKind::GroupTableFull
+        | BridgeErrorKind::SceneBufferFull
+        | BridgeErrorKind::SensorListFull
+        | BridgeErrorKind::RuleEngineFull
+        | BridgeErrorKind::ScheduleListFull => StatusCode::CONFLICT,
+        BridgeErrorKind::DeviceUnreachable => StatusCode::SERVICE_UNAVAILABLE,
+        // The server's own credentials were refused, which isn't something the client can fix
+        BridgeErrorKind::UnauthorizedUser
+        | BridgeErrorKind::LinkButtonNotPressed
+        | BridgeErrorKind::PortalConnectionRequired
+        | BridgeErrorKind::InternalError
+        | BridgeErrorKind::Other(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// Turns requests no route accepted into the same error body handlers use
+pub async fn handle_rejection(rejection: Rejection) -> Result<Box<dyn warp::Reply>, Infallible> {
+    if rejection.find::<Unauthorized>().is_some() {
+        let api_error = ApiError::new(StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized, "Missing or unknown API token");
+        let challenge = r#"Basic realm="hoo", charset="UTF-8""#;
+        return Ok(Box::new(warp::reply::with_header(error(api_error), "www-authenticate", challenge)));
+    }
+
+    let api_error = if let Some(Forbidden(message)) = rejection.find::<Forbidden>() {
+        ApiError::new(StatusCode::FORBIDDEN, ErrorCode::Forbidden, message)
+    } else if let Some(e) = rejection.find::<warp::body::BodyDeserializeError>() {
+        ApiError::bad_request(&e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::InvalidQuery>() {
+        ApiError::bad_request(&e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::UnsupportedMediaType>() {
+        ApiError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, ErrorCode::UnsupportedMediaType, &e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::PayloadTooLarge>() {
+        ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, ErrorCode::PayloadTooLarge, &e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::LengthRequired>() {
+        ApiError::bad_request(&e.to_string())
+    } else if let Some(e) = rejection.find::<warp::reject::MethodNotAllowed>() {
+        ApiError::new(StatusCode::METHOD_NOT_ALLOWED, ErrorCode::MethodNotAllowed, &e.to_string())
+    } else if rejection.is_not_found() {
+        ApiError::not_found("No such endpoint")
+    } else {
+        ApiError::internal(&format!("Unhandled rejection: {:?}", rejection))
+    };
+
+    Ok(Box::new(error(api_error)))
+}
@@ -0,0 +1,77 @@
+use std::convert::Infallible;
+
+use warp::Filter;
+
+use hoo_api::{HueClient, Rule};
+
+use crate::reply::{self, Created};
+
+pub fn routes(client: HueClient) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let client_clone = client.clone();
+    let all_rules = warp::path!("rules")
+        .and(warp::get())
+        .and_then(move || get_all_rules(client_clone.clone()));
+
+    let client_clone = client.clone();
+    let get_rule = warp::path!("rules" / String)
+        .and(warp::get())
+        .and_then(move |rule_id| get_rule(client_clone.clone(), rule_id));
+
+    let client_clone = client.clone();
+    let create_rule = warp::path!("rules")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(move |rule| create_rule(client_clone.clone(), rule));
+
+    let client_clone = client.clone();
+    let update_rule = warp::path!("rules" / String)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and_then(move |rule_id, rule| update_rule(client_clone.clone(), rule_id, rule));
+
+    let client_clone = client;
+    let delete_rule = warp::path!("rules" / String)
+        .and(warp::delete())
+        .and_then(move |rule_id| delete_rule(client_clone.clone(), rule_id));
+
+    all_rules
+        .or(get_rule)
+        .or(create_rule)
+        .or(update_rule)
+        .or(delete_rule)
+}
+
+async fn get_all_rules(client: HueClient) -> Result<impl warp::Reply, Infallible> {
+    match client.get_all_rules().await {
+        Ok(rules) => Ok(reply::ok(&rules)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn get_rule(client: HueClient, rule_id: String) -> Result<impl warp::Reply, Infallible> {
+    match client.get_rule(&rule_id).await {
+        Ok(rule) => Ok(reply::ok(&rule)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn create_rule(client: HueClient, rule: Rule) -> Result<impl warp::Reply, Infallible> {
+    match client.create_rule(&rule).await {
+        Ok(rule_id) => Ok(reply::created(&Created { id: rule_id })),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn update_rule(client: HueClient, rule_id: String, rule: Rule) -> Result<impl warp::Reply, Infallible> {
+    match client.update_rule(&rule_id, &rule).await {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn delete_rule(client: HueClient, rule_id: String) -> Result<impl warp::Reply, Infallible> {
+    match client.delete_rule(&rule_id).await {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
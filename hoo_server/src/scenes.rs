@@ -0,0 +1,157 @@
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use warp::Filter;
+
+use hoo_api::{GroupNumber, HueClient, LightBackend, SceneStore};
+
+use crate::batch_reply;
+use crate::cache::LightCache;
+use crate::reply::{self, ApiError, Deleted};
+
+type SharedSceneStore = Arc<Mutex<SceneStore>>;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct ApplyQuery {
+    transitiontime: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct RecallQuery {
+    group: Option<GroupNumber>,
+}
+
+/// Scenes saved to a file, which work with any backend
+pub fn routes<B: LightBackend>(backend: B, store: SceneStore, cache: LightCache<B>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let store = Arc::new(Mutex::new(store));
+
+    let store_clone = store.clone();
+    let saved_scenes = warp::path!("scenes")
+        .and(warp::get())
+        .and_then(move || get_saved_scenes(store_clone.clone()));
+
+    let backend_clone = backend;
+    let store_clone = store.clone();
+    let save_scene = warp::path!("scenes" / String)
+        .and(warp::post())
+        .and_then(move |name: String| save_scene(backend_clone.clone(), store_clone.clone(), decode(&name)));
+
+    let store_clone = store.clone();
+    let cache_clone = cache;
+    let apply_scene = warp::path!("scenes" / String / "apply")
+        .and(warp::put())
+        .and(warp::query::query())
+        .and_then(move |name: String, query: ApplyQuery| {
+            apply_scene(store_clone.clone(), cache_clone.clone(), decode(&name), query.transitiontime)
+        });
+
+    let store_clone = store;
+    let delete_scene = warp::path!("scenes" / String)
+        .and(warp::delete())
+        .and_then(move |name: String| delete_scene(store_clone.clone(), decode(&name)));
+
+    saved_scenes
+        .or(save_scene)
+        .or(apply_scene)
+        .or(delete_scene)
+}
+
+/// Scenes stored on the Hue bridge. Has to come before `routes`, since `scenes/bridge` is also a saved scene path.
+pub fn bridge_routes(client: HueClient, cache: LightCache<HueClient>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let client_clone = client.clone();
+    let bridge_scenes = warp::path!("scenes" / "bridge")
+        .and(warp::get())
+        .and_then(move || get_bridge_scenes(client_clone.clone()));
+
+    let client_clone = client;
+    let cache_clone = cache;
+    let recall_scene = warp::path!("scenes" / "bridge" / String / "recall")
+        .and(warp::put())
+        .and(warp::query::query())
+        .and_then(move |scene_id: String, query: RecallQuery| {
+            recall_scene(client_clone.clone(), cache_clone.clone(), scene_id, query.group)
+        });
+
+    bridge_scenes.or(recall_scene)
+}
+
+fn decode(name: &str) -> String {
+    percent_decode_str(name).decode_utf8_lossy().to_string()
+}
+
+async fn get_saved_scenes(store: SharedSceneStore) -> Result<impl warp::Reply, Infallible> {
+    match store.lock().unwrap().load() {
+        Ok(scenes) => Ok(reply::ok(&scenes)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn get_bridge_scenes(client: HueClient) -> Result<impl warp::Reply, Infallible> {
+    match client.get_all_scenes().await {
+        Ok(scenes) => Ok(reply::ok(&scenes)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn save_scene<B: LightBackend>(backend: B, store: SharedSceneStore, name: String) -> Result<impl warp::Reply, Infallible> {
+    let scene = match backend.capture_scene(&name, &[]).await {
+        Ok(scene) => scene,
+        Err(e) => return Ok(reply::error(e)),
+    };
+
+    match store.lock().unwrap().save(scene.clone()) {
+        Ok(_) => Ok(reply::created(&scene)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+/// Goes through the cache like any other batch of writes, which adds the profile's transition time when the request
+/// doesn't give one
+async fn apply_scene<B: LightBackend>(
+    store: SharedSceneStore,
+    cache: LightCache<B>,
+    name: String,
+    transition_time: Option<u16>,
+) -> Result<impl warp::Reply, Infallible> {
+    let scene = match store.lock().unwrap().get(&name) {
+        Ok(Some(scene)) => scene,
+        Ok(None) => return Ok(reply::error(ApiError::not_found(&format!("No saved scene named {}", name)))),
+        Err(e) => return Ok(reply::error(e)),
+    };
+
+    let mut states: Vec<_> = scene
+        .states
+        .into_iter()
+        .map(|(light_num, mut state)| {
+            state.transitiontime = transition_time;
+            (light_num, state)
+        })
+        .collect();
+    states.sort_unstable_by_key(|(light_num, _)| *light_num);
+    Ok(batch_reply(&cache, &states).await)
+}
+
+async fn delete_scene(store: SharedSceneStore, name: String) -> Result<impl warp::Reply, Infallible> {
+    match store.lock().unwrap().remove(&name) {
+        Ok(true) => Ok(reply::ok(&Deleted { deleted: name })),
+        Ok(false) => Ok(reply::error(ApiError::not_found(&format!("No saved scene named {}", name)))),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn recall_scene(
+    client: HueClient,
+    cache: LightCache<HueClient>,
+    scene_id: String,
+    group: Option<GroupNumber>,
+) -> Result<impl warp::Reply, Infallible> {
+    let result = client.recall_scene(&scene_id, group).await;
+    cache.invalidate();
+
+    match result {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
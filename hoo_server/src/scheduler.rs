@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+
+use hoo_api::{LightBackend, LightNumber, LightState};
+
+use crate::cache::LightCache;
+use crate::sun::{sun_event, SunEvent};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Enough to get past a couple of days without a sunrise or sunset near the poles
+const SUN_EVENT_SEARCH_DAYS: i64 = 3;
+
+pub type JobId = u32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    #[serde(default)]
+    pub id: JobId,
+    pub name: String,
+    pub trigger: Trigger,
+    pub lights: Vec<LightNumber>,
+    pub state: LightState,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Job {
+    /// Catches what would make the job fail every time it runs, before it's saved
+    fn validate(&self, location: Option<Location>) -> Result<()> {
+        self.trigger.validate(location)?;
+        if self.lights.is_empty() {
+            return Err(anyhow!("Jobs need at least one light"));
+        }
+        self.state.validate()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Trigger {
+    /// A cron expression in local time with a leading seconds field, e.g. `0 0 7 * * *`
+    Cron { expression: String },
+    Sunrise {
+        #[serde(default)]
+        offset_minutes: i64,
+    },
+    Sunset {
+        #[serde(default)]
+        offset_minutes: i64,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Trigger {
+    fn validate(&self, location: Option<Location>) -> Result<()> {
+        match self {
+            Trigger::Cron { expression } => {
+                cron::Schedule::from_str(expression)
+                    .map_err(|e| anyhow!("Invalid cron expression {}: {}", expression, e))?;
+            }
+            Trigger::Sunrise { .. } | Trigger::Sunset { .. } => {
+                if location.is_none() {
+                    return Err(anyhow!("Sunrise and sunset jobs need a latitude and longitude"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The first time this trigger fires strictly after `after`
+    fn next_after(&self, after: DateTime<Local>, location: Option<Location>) -> Option<DateTime<Local>> {
+        match self {
+            Trigger::Cron { expression } => cron::Schedule::from_str(expression).ok()?.after(&after).next(),
+            Trigger::Sunrise { offset_minutes } => {
+                next_sun_event(SunEvent::Sunrise, *offset_minutes, after, location?)
+            }
+            Trigger::Sunset { offset_minutes } => {
+                next_sun_event(SunEvent::Sunset, *offset_minutes, after, location?)
+            }
+        }
+    }
+}
+
+fn next_sun_event(
+    event: SunEvent,
+    offset_minutes: i64,
+    after: DateTime<Local>,
+    location: Location,
+) -> Option<DateTime<Local>> {
+    let today = after.date_naive();
+
+    (-1..SUN_EVENT_SEARCH_DAYS)
+        .filter_map(|days| {
+            let date = today + ChronoDuration::days(days);
+            sun_event(date, location.latitude, location.longitude, event)
+        })
+        .map(|time| time.with_timezone(&Local) + ChronoDuration::minutes(offset_minutes))
+        .find(|time| *time > after)
+}
+
+/// A job along with when it will next run
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    #[serde(flatten)]
+    pub job: Job,
+    pub next_run: Option<String>,
+}
+
+/// Runs light state changes at cron or sunrise/sunset-relative times. Jobs are kept in a JSON file.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    jobs: Arc<Mutex<BTreeMap<JobId, Job>>>,
+    path: PathBuf,
+    location: Option<Location>,
+}
+
+impl Scheduler {
+    /// Starts with no jobs if the file doesn't exist yet
+    pub fn load<P: AsRef<Path>>(path: P, location: Option<Location>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let jobs: Vec<Job> = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            jobs: Arc::new(Mutex::new(jobs.into_iter().map(|job| (job.id, job)).collect())),
+            path,
+            location,
+        })
+    }
+
+    pub fn jobs(&self) -> Vec<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.values().map(|job| self.status(job.clone())).collect()
+    }
+
+    pub fn get(&self, id: JobId) -> Option<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.get(&id).map(|job| self.status(job.clone()))
+    }
+
+    /// Assigns the job a new id
+    pub fn add(&self, mut job: Job) -> Result<JobStatus> {
+        job.validate(self.location)?;
+
+        let mut jobs = self.jobs.lock().unwrap();
+        job.id = jobs.keys().next_back().map_or(1, |id| id + 1);
+        jobs.insert(job.id, job.clone());
+        self.persist(&jobs)?;
+
+        Ok(self.status(job))
+    }
+
+    pub fn update(&self, id: JobId, mut job: Job) -> Result<Option<JobStatus>> {
+        job.validate(self.location)?;
+        job.id = id;
+
+        let mut jobs = self.jobs.lock().unwrap();
+        if !jobs.contains_key(&id) {
+            return Ok(None);
+        }
+        jobs.insert(id, job.clone());
+        self.persist(&jobs)?;
+
+        Ok(Some(self.status(job)))
+    }
+
+    /// Returns whether a job with that id existed
+    pub fn remove(&self, id: JobId) -> Result<bool> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let existed = jobs.remove(&id).is_some();
+        if existed {
+            self.persist(&jobs)?;
+        }
+        Ok(existed)
+    }
+
+    /// Writes go through the cache, so they share the rate limited queue with everything else
+    pub fn spawn<B: LightBackend>(&self, cache: LightCache<B>) {
+        tokio::spawn(self.clone().run(cache));
+    }
+
+    async fn run<B: LightBackend>(self, cache: LightCache<B>) {
+        let mut last_check = Local::now();
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let now = Local::now();
+
+            let due: Vec<Job> = {
+                let jobs = self.jobs.lock().unwrap();
+                jobs.values()
+                    .filter(|job| job.enabled)
+                    .filter(|job| {
+                        job.trigger
+                            .next_after(last_check, self.location)
+                            .is_some_and(|time| time <= now)
+                    })
+                    .cloned()
+                    .collect()
+            };
+            last_check = now;
+
+            // A job waiting on a slow bridge shouldn't hold up the ticks, or the jobs due after it
+            for job in due {
+                tokio::spawn(execute(cache.clone(), job));
+            }
+        }
+    }
+
+    fn status(&self, job: Job) -> JobStatus {
+        let next_run = if job.enabled {
+            job.trigger
+                .next_after(Local::now(), self.location)
+                .map(|time| time.to_rfc3339())
+        } else {
+            None
+        };
+
+        JobStatus { job, next_run }
+    }
+
+    fn persist(&self, jobs: &BTreeMap<JobId, Job>) -> Result<()> {
+        let jobs: Vec<&Job> = jobs.values().collect();
+        std::fs::write(&self.path, serde_json::to_string_pretty(&jobs)?)?;
+        Ok(())
+    }
+}
+
+async fn execute<B: LightBackend>(cache: LightCache<B>, job: Job) {
+    println!("Running scheduled job {} ({})", job.id, job.name);
+    for light_num in &job.lights {
+        if let Err(e) = cache.set_state(*light_num, &job.state).await {
+            eprintln!("Scheduled job {} failed for light {}: {}", job.id, light_num, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    const LONDON: Location = Location {
+        latitude: 51.5,
+        longitude: -0.13,
+    };
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap().with_timezone(&Local)
+    }
+
+    fn cron(expression: &str) -> Trigger {
+        Trigger::Cron {
+            expression: expression.to_string(),
+        }
+    }
+
+    #[test]
+    fn cron_runs_strictly_after() {
+        let trigger = cron("0 0 7 * * *");
+
+        assert_eq!(trigger.next_after(local(2024, 6, 1, 6, 0), None), Some(local(2024, 6, 1, 7, 0)));
+        assert_eq!(trigger.next_after(local(2024, 6, 1, 7, 0), None), Some(local(2024, 6, 2, 7, 0)));
+        assert_eq!(cron("not cron").next_after(local(2024, 6, 1, 6, 0), None), None);
+    }
+
+    #[test]
+    fn sunrise_is_found_with_its_offset() {
+        let sunrise = Trigger::Sunrise { offset_minutes: 0 }
+            .next_after(utc(2024, 6, 1, 0, 0), Some(LONDON))
+            .unwrap();
+        // London's midsummer sunrise is around a quarter to four UTC
+        assert!(sunrise > utc(2024, 6, 1, 3, 30) && sunrise < utc(2024, 6, 1, 4, 0), "{}", sunrise);
+
+        let offset = Trigger::Sunrise { offset_minutes: -30 }
+            .next_after(utc(2024, 6, 1, 0, 0), Some(LONDON))
+            .unwrap();
+        assert_eq!(sunrise - offset, ChronoDuration::minutes(30));
+
+        // Once today's has passed, it's tomorrow's
+        let tomorrow = Trigger::Sunrise { offset_minutes: 0 }
+            .next_after(sunrise, Some(LONDON))
+            .unwrap();
+        assert!(tomorrow - sunrise > ChronoDuration::hours(23), "{}", tomorrow);
+        assert!(tomorrow - sunrise < ChronoDuration::hours(25), "{}", tomorrow);
+    }
+
+    #[test]
+    fn sunset_needs_a_location_and_a_sunset() {
+        let sunset = Trigger::Sunset { offset_minutes: 0 };
+        assert!(sunset.next_after(utc(2024, 6, 1, 0, 0), None).is_none());
+
+        // Svalbard has no sunset in June
+        let svalbard = Location {
+            latitude: 78.2,
+            longitude: 15.6,
+        };
+        assert!(sunset.next_after(utc(2024, 6, 1, 0, 0), Some(svalbard)).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+use std::convert::Infallible;
+
+use warp::Filter;
+
+use hoo_api::{HueClient, Schedule};
+
+use crate::scheduler::{Job, JobId, Scheduler};
+use crate::reply::{self, ApiError, Created, Deleted};
+
+pub fn routes(client: HueClient, scheduler: Scheduler) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let scheduler_clone = scheduler.clone();
+    let all_jobs = warp::path!("schedules")
+        .and(warp::get())
+        .and_then(move || get_all_jobs(scheduler_clone.clone()));
+
+    let scheduler_clone = scheduler.clone();
+    let get_job = warp::path!("schedules" / JobId)
+        .and(warp::get())
+        .and_then(move |id| get_job(scheduler_clone.clone(), id));
+
+    let scheduler_clone = scheduler.clone();
+    let add_job = warp::path!("schedules")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(move |job| add_job(scheduler_clone.clone(), job));
+
+    let scheduler_clone = scheduler.clone();
+    let update_job = warp::path!("schedules" / JobId)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and_then(move |id, job| update_job(scheduler_clone.clone(), id, job));
+
+    let scheduler_clone = scheduler;
+    let delete_job = warp::path!("schedules" / JobId)
+        .and(warp::delete())
+        .and_then(move |id| delete_job(scheduler_clone.clone(), id));
+
+    let client_clone = client.clone();
+    let bridge_schedules = warp::path!("schedules" / "bridge")
+        .and(warp::get())
+        .and_then(move || get_bridge_schedules(client_clone.clone()));
+
+    let client_clone = client.clone();
+    let create_bridge_schedule = warp::path!("schedules" / "bridge")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(move |schedule| create_bridge_schedule(client_clone.clone(), schedule));
+
+    let client_clone = client;
+    let delete_bridge_schedule = warp::path!("schedules" / "bridge" / String)
+        .and(warp::delete())
+        .and_then(move |schedule_id| delete_bridge_schedule(client_clone.clone(), schedule_id));
+
+    bridge_schedules
+        .or(create_bridge_schedule)
+        .or(delete_bridge_schedule)
+        .or(all_jobs)
+        .or(get_job)
+        .or(add_job)
+        .or(update_job)
+        .or(delete_job)
+}
+
+async fn get_all_jobs(scheduler: Scheduler) -> Result<impl warp::Reply, Infallible> {
+    Ok(reply::ok(&scheduler.jobs()))
+}
+
+async fn get_job(scheduler: Scheduler, id: JobId) -> Result<impl warp::Reply, Infallible> {
+    match scheduler.get(id) {
+        Some(job) => Ok(reply::ok(&job)),
+        None => Ok(reply::error(ApiError::not_found(&format!("No scheduled job with id {}", id)))),
+    }
+}
+
+async fn add_job(scheduler: Scheduler, job: Job) -> Result<impl warp::Reply, Infallible> {
+    match scheduler.add(job) {
+        Ok(job) => Ok(reply::created(&job)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn update_job(scheduler: Scheduler, id: JobId, job: Job) -> Result<impl warp::Reply, Infallible> {
+    match scheduler.update(id, job) {
+        Ok(Some(job)) => Ok(reply::ok(&job)),
+        Ok(None) => Ok(reply::error(ApiError::not_found(&format!("No scheduled job with id {}", id)))),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn delete_job(scheduler: Scheduler, id: JobId) -> Result<impl warp::Reply, Infallible> {
+    match scheduler.remove(id) {
+        Ok(true) => Ok(reply::ok(&Deleted { deleted: id })),
+        Ok(false) => Ok(reply::error(ApiError::not_found(&format!("No scheduled job with id {}", id)))),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn get_bridge_schedules(client: HueClient) -> Result<impl warp::Reply, Infallible> {
+    match client.get_all_schedules().await {
+        Ok(schedules) => Ok(reply::ok(&schedules)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn create_bridge_schedule(client: HueClient, schedule: Schedule) -> Result<impl warp::Reply, Infallible> {
+    match client.create_schedule(&schedule).await {
+        Ok(schedule_id) => Ok(reply::created(&Created { id: schedule_id })),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn delete_bridge_schedule(client: HueClient, schedule_id: String) -> Result<impl warp::Reply, Infallible> {
+    match client.delete_schedule(&schedule_id).await {
+        Ok(result) => Ok(reply::ok(&result)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use percent_encoding::percent_decode_str;
+use warp::Filter;
+
+use hoo_api::{Light, LightBackend, LightCollection, LightSelector, LightState};
+
+use crate::batch_reply;
+use crate::cache::{Freshness, LightCache};
+use crate::reply::{self, ApiError};
+
+/// Light aliases from the config file
+pub type Aliases = Arc<HashMap<String, LightSelector>>;
+
+/// Routes that take a light selector like `3,5-7`, `name:Kitchen*` or `group:Living%20room` in place of one light
+pub fn routes<B: LightBackend>(cache: LightCache<B>, aliases: Aliases) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let with_aliases = warp::any().map(move || aliases.clone());
+
+    let cache_clone = cache.clone();
+    let selected_lights = warp::path!("lights" / String)
+        .and(warp::get())
+        .and(with_aliases.clone())
+        .and(warp::query::query())
+        .and_then(move |selector: String, aliases, freshness| {
+            get_lights(cache_clone.clone(), aliases, selector, freshness)
+        });
+
+    let cache_clone = cache.clone();
+    let selected_on = warp::path!("lights" / String / "on")
+        .and(warp::put())
+        .and(with_aliases.clone())
+        .and_then(move |selector: String, aliases| {
+            set_each(cache_clone.clone(), aliases, selector, |_| LightState::new().on(true))
+        });
+
+    let cache_clone = cache.clone();
+    let selected_off = warp::path!("lights" / String / "off")
+        .and(warp::put())
+        .and(with_aliases.clone())
+        .and_then(move |selector: String, aliases| {
+            set_each(cache_clone.clone(), aliases, selector, |_| LightState::new().on(false))
+        });
+
+    let cache_clone = cache.clone();
+    let selected_toggle = warp::path!("lights" / String / "toggle")
+        .and(warp::put())
+        .and(with_aliases.clone())
+        .and_then(move |selector: String, aliases| {
+            set_each(cache_clone.clone(), aliases, selector, |light| LightState::new().on(!light.state.is_on()))
+        });
+
+    let cache_clone = cache;
+    let selected_state = warp::path!("lights" / String / "state")
+        .and(warp::put())
+        .and(with_aliases)
+        .and(warp::body::json())
+        .and_then(move |selector: String, aliases, state| set_state(cache_clone.clone(), aliases, selector, state));
+
+    selected_lights
+        .or(selected_on)
+        .or(selected_off)
+        .or(selected_toggle)
+        .or(selected_state)
+}
+
+/// Parses a selector from a path segment, expands any aliases from the config file and looks up the lights it
+/// matches. Matching nothing is a 404.
+async fn select<B: LightBackend>(cache: &LightCache<B>, aliases: &Aliases, selector: &str, freshness: Freshness) -> Result<LightCollection, ApiError> {
+    let selector: LightSelector = percent_decode_str(selector).decode_utf8_lossy().parse()?;
+    let selector = selector.expand_aliases(aliases)?;
+    let lights = cache.select(&selector, freshness).await?;
+    if lights.is_empty() {
+        return Err(ApiError::not_found(&format!("No lights match {}", selector)));
+    }
+    Ok(lights)
+}
+
+async fn get_lights<B: LightBackend>(cache: LightCache<B>, aliases: Aliases, selector: String, freshness: Freshness) -> Result<impl warp::Reply, Infallible> {
+    match select(&cache, &aliases, &selector, freshness).await {
+        Ok(lights) => Ok(reply::ok(&lights)),
+        Err(e) => Ok(reply::error(e)),
+    }
+}
+
+async fn set_state<B: LightBackend>(cache: LightCache<B>, aliases: Aliases, selector: String, state: LightState) -> Result<reply::Reply, Infallible> {
+    if let Err(e) = state.validate() {
+        return Ok(reply::error(e));
+    }
+    set_each(cache, aliases, selector, move |_| state.clone()).await
+}
+
+/// Sets every selected light to a state worked out from its cached one
+async fn set_each<B, F>(cache: LightCache<B>, aliases: Aliases, selector: String, state_for: F) -> Result<reply::Reply, Infallible>
+where
+    B: LightBackend,
+    F: Fn(&Light) -> LightState,
+{
+    let lights = match select(&cache, &aliases, &selector, Freshness::default()).await {
+        Ok(lights) => lights,
+        Err(e) => return Ok(reply::error(e)),
+    };
+
+    let mut states: Vec<_> = lights.iter().map(|(light_num, light)| (*light_num, state_for(light))).collect();
+    states.sort_unstable_by_key(|(light_num, _)| *light_num);
+    Ok(batch_reply(&cache, &states).await)
+}
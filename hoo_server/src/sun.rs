@@ -0,0 +1,50 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+const J2000: f64 = 2_451_545.0;
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+/// Computes sunrise or sunset for a date using the sunrise equation.
+/// Longitude is positive east of Greenwich. Returns `None` during polar day or night.
+pub fn sun_event(date: NaiveDate, latitude: f64, longitude: f64, event: SunEvent) -> Option<DateTime<Utc>> {
+    let midnight = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?);
+    let julian_day = midnight.timestamp() as f64 / SECONDS_PER_DAY + UNIX_EPOCH_JULIAN_DAY;
+
+    let day_number = (julian_day - J2000 + 0.0008).ceil();
+    let mean_solar_noon = day_number - longitude / 360.0;
+
+    let mean_anomaly = (357.5291 + 0.985_600_28 * mean_solar_noon).rem_euclid(360.0).to_radians();
+    let center = 1.9148 * mean_anomaly.sin()
+        + 0.02 * (2.0 * mean_anomaly).sin()
+        + 0.0003 * (3.0 * mean_anomaly).sin();
+    let ecliptic_longitude = (mean_anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let solar_transit = J2000 + mean_solar_noon + 0.0053 * mean_anomaly.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+    let event_day = match event {
+        SunEvent::Sunrise => solar_transit - hour_angle,
+        SunEvent::Sunset => solar_transit + hour_angle,
+    };
+
+    let timestamp = (event_day - UNIX_EPOCH_JULIAN_DAY) * SECONDS_PER_DAY;
+    Utc.timestamp_opt(timestamp.round() as i64, 0).single()
+}
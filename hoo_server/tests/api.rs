@@ -0,0 +1,331 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use hyper::{Body, Client, Request, StatusCode};
+use serde_json::Value;
+use tokio::process::{Child, Command};
+
+use hoo_api::{hash_token, LightState};
+use hoo_mock_bridge::{MockBridge, USER_ID};
+
+/// A `hoo_server` pointed at a mock bridge, killed when dropped
+struct Server {
+    _child: Child,
+    addr: SocketAddr,
+    dir: PathBuf,
+}
+
+impl Server {
+    /// Starts the server in an empty directory with `config` as its config file, and waits for it to listen
+    async fn start(bridge: &MockBridge, test: &str, config: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("hoo_server_{}_{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.toml");
+        std::fs::write(&config_path, config).unwrap();
+
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_hoo_server"))
+            .current_dir(&dir)
+            .env("HUE_BASE_URI", bridge.base_uri())
+            .env("HUE_USER_ID", USER_ID)
+            .env("HOO_CONFIG", &config_path)
+            .env("HOO_PORT", addr.port().to_string())
+            .env_remove("HOO_PROFILE")
+            .env_remove("HOO_ADDRESS")
+            .env_remove("HOO_SOCKET")
+            .env_remove("HOO_CORS_ORIGINS")
+            .env_remove("HOO_BRIDGE_TIMEOUT")
+            .env_remove("HOO_BRIDGE_RETRIES")
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+
+        for _ in 0..100 {
+            if TcpStream::connect(addr).is_ok() {
+                return Self { _child: child, addr, dir };
+            }
+            tokio::time::delay_for(Duration::from_millis(50)).await;
+        }
+        panic!("hoo_server didn't start listening on {}", addr);
+    }
+
+    async fn request(&self, method: &str, path: &str, token: Option<&str>, body: Option<&str>) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("http://{}{}", self.addr, path));
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        if body.is_some() {
+            request = request.header("content-type", "application/json");
+        }
+        let request = request.body(Body::from(body.unwrap_or_default().to_string())).unwrap();
+
+        let response = Client::new().request(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+}
+
+/// Runs a server that's expected to refuse to start, and returns what it printed
+async fn start_failure(bridge: &MockBridge, test: &str, config: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("hoo_server_{}_{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("config.toml");
+    std::fs::write(&config_path, config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hoo_server"))
+        .current_dir(&dir)
+        .env("HUE_BASE_URI", bridge.base_uri())
+        .env("HUE_USER_ID", USER_ID)
+        .env("HOO_CONFIG", &config_path)
+        .env("HOO_PORT", "0")
+        .env("RUST_BACKTRACE", "0")
+        .env_remove("HOO_PROFILE")
+        .env_remove("HOO_SOCKET")
+        .output()
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert!(!output.status.success());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// One of the mock bridge's test certificates or keys
+fn mock_cert(file: &str) -> String {
+    format!("{}/../hoo_mock_bridge/certs/{}", env!("CARGO_MANIFEST_DIR"), file)
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+#[tokio::test]
+async fn serves_lights() {
+    let bridge = MockBridge::with_sample_home();
+    let server = Server::start(&bridge, "lights", "").await;
+
+    let (status, lights) = server.request("GET", "/api/lights", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(lights.as_object().unwrap().len(), 3);
+    assert_eq!(lights["3"]["name"], "Kitchen");
+
+    let (status, error) = server.request("GET", "/api/light/9", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "bridge_error");
+}
+
+#[tokio::test]
+async fn sets_light_state() {
+    let bridge = MockBridge::with_sample_home();
+    let server = Server::start(&bridge, "state", "").await;
+
+    let (status, outcome) = server.request("PUT", "/api/light/2/on", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(outcome["outcome"], "sent");
+    assert!(bridge.light(2).unwrap().state.is_on());
+
+    let (status, _) = server.request("PUT", "/api/light/3/state", None, Some(r#"{"bri":40}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bridge.light(3).unwrap().state.bri, Some(40));
+
+    let (status, error) = server.request("PUT", "/api/light/3/state", None, Some(r#"{"bri":0}"#)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn increments_are_read_back_from_the_bridge() {
+    let bridge = MockBridge::with_sample_home();
+    let server = Server::start(&bridge, "increment", "").await;
+    server.request("GET", "/api/lights", None, None).await;
+
+    let (status, _) = server.request("PUT", "/api/light/1/state", None, Some(r#"{"bri_inc":20}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, light) = server.request("GET", "/api/light/1", None, None).await;
+    assert_eq!(light["state"]["bri"], 120);
+    assert!(light["state"].get("bri_inc").is_none(), "{}", light);
+}
+
+#[tokio::test]
+async fn selector_routes_report_each_light() {
+    let bridge = MockBridge::with_sample_home();
+    bridge.set_reachable(2, false);
+    let server = Server::start(&bridge, "selector", "").await;
+
+    let (status, results) = server.request("PUT", "/api/lights/group:living%20room/off", None, None).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!(results["1"]["outcome"], "sent");
+    assert_eq!(results["2"]["code"], "bridge_error");
+    assert!(!bridge.light(1).unwrap().state.is_on());
+
+    let (status, _) = server.request("GET", "/api/lights/name:nothing*", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn cached_lights_follow_the_watcher() {
+    let bridge = MockBridge::with_sample_home();
+    let server = Server::start(&bridge, "follow", "").await;
+
+    let (_, lights) = server.request("GET", "/api/lights", None, None).await;
+    assert_eq!(lights["2"]["state"]["on"], false);
+
+    // Switched on from somewhere else, and picked up by the next poll well before the cache expires
+    bridge.add_light(2, "Lamp", LightState::new().on(true));
+    tokio::time::delay_for(Duration::from_millis(1500)).await;
+    let (_, lights) = server.request("GET", "/api/lights", None, None).await;
+    assert_eq!(lights["2"]["state"]["on"], true);
+}
+
+#[tokio::test]
+async fn scheduled_jobs_write_through_the_queue() {
+    let bridge = MockBridge::with_sample_home();
+    let config = "[profiles.default]\ntransition_time = 7\n";
+    let server = Server::start(&bridge, "schedule", config).await;
+
+    let job = r#"{"name":"Every second","trigger":{"type":"cron","expression":"* * * * * *"},"lights":[2],"state":{"on":true}}"#;
+    let (status, _) = server.request("POST", "/api/schedules", None, Some(job)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    tokio::time::delay_for(Duration::from_millis(2500)).await;
+
+    assert!(bridge.light(2).unwrap().state.is_on());
+    // The profile's transition time is added by the light cache
+    let write = bridge
+        .requests()
+        .into_iter()
+        .find(|request| request.method == "PUT" && request.path.ends_with("/lights/2/state"))
+        .unwrap();
+    assert!(write.body.contains(r#""transitiontime":7"#), "{}", write.body);
+}
+
+#[tokio::test]
+async fn rejects_jobs_that_cant_run() {
+    let bridge = MockBridge::with_sample_home();
+    let server = Server::start(&bridge, "bad_jobs", "").await;
+
+    let trigger = r#""trigger":{"type":"cron","expression":"0 0 7 * * *"}"#;
+    for job in &[
+        format!(r#"{{"name":"Dim",{},"lights":[1],"state":{{"bri":0}}}}"#, trigger),
+        format!(r#"{{"name":"Nothing",{},"lights":[],"state":{{"on":true}}}}"#, trigger),
+        r#"{"name":"Never","trigger":{"type":"cron","expression":"not cron"},"lights":[1],"state":{"on":true}}"#
+            .to_string(),
+    ] {
+        let (status, error) = server.request("POST", "/api/schedules", None, Some(job)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", job);
+        assert_eq!(error["code"], "bad_request");
+    }
+
+    let (_, jobs) = server.request("GET", "/api/schedules", None, None).await;
+    assert_eq!(jobs, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn applies_saved_scenes_through_the_queue() {
+    let bridge = MockBridge::with_sample_home();
+    let server = Server::start(&bridge, "scenes", "").await;
+
+    let (status, _) = server.request("POST", "/api/scenes/Evening", None, None).await;
+    assert_eq!(status, StatusCode::CREATED);
+    server.request("PUT", "/api/light/3/state", None, Some(r#"{"bri":40}"#)).await;
+
+    bridge.clear_requests();
+    let (status, results) = server.request("PUT", "/api/scenes/Evening/apply?transitiontime=2", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(results["3"]["outcome"], "sent");
+    assert_eq!(bridge.light(3).unwrap().state.bri, Some(254));
+    assert!(bridge.requests().iter().all(|request| request.body.contains(r#""transitiontime":2"#)));
+}
+
+#[tokio::test]
+async fn times_out_slow_bridges() {
+    let bridge = MockBridge::with_sample_home();
+    let config = "[profiles.default]\nbridge_timeout = 100\nbridge_retries = 0\n";
+    let server = Server::start(&bridge, "timeout", config).await;
+    bridge.set_latency(Duration::from_secs(1));
+
+    // Groups aren't cached, so this has to go to the bridge
+    let (status, error) = server.request("GET", "/api/groups", None, None).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(error["code"], "bridge_timeout");
+}
+
+#[tokio::test]
+async fn checks_tokens() {
+    let bridge = MockBridge::with_sample_home();
+    let config = format!(
+        r#"
+        [profiles.default]
+        [[profiles.default.tokens]]
+        name = "viewer"
+        hash = "{}"
+        permission = "read"
+
+        [[profiles.default.tokens]]
+        name = "phone"
+        hash = "{}"
+        permission = "control"
+        lights = "1"
+        "#,
+        hash_token("viewer-token"),
+        hash_token("phone-token"),
+    );
+    let server = Server::start(&bridge, "tokens", &config).await;
+
+    let (status, _) = server.request("GET", "/api/lights", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = server.request("GET", "/api/lights", Some("viewer-token"), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = server.request("PUT", "/api/light/1/on", Some("viewer-token"), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Animations change every light, so they need a token that can control all of them
+    let (status, _) = server.request("PUT", "/api/rotate/10/10", Some("viewer-token"), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = server.request("PUT", "/api/stop", Some("phone-token"), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = server.request("GET", "/api/rotate/10/10", Some("viewer-token"), None).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+    let (status, _) = server.request("PUT", "/api/light/2/on", Some("phone-token"), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = server.request("PUT", "/api/light/1/off", Some("phone-token"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!bridge.light(1).unwrap().state.is_on());
+}
+
+#[tokio::test]
+async fn checks_tls_settings_before_starting() {
+    let bridge = MockBridge::with_sample_home();
+    let tls = |cert: &str, key: &str| {
+        format!("[profiles.default]\ntls_cert = {:?}\ntls_key = {:?}\n", mock_cert(cert), mock_cert(key))
+    };
+
+    // A working pair starts up
+    let _server = Server::start(&bridge, "tls", &tls("bridge.pem", "bridge.key")).await;
+
+    let stderr = start_failure(&bridge, "tls_no_key", &tls("bridge.pem", "bridge.pem")).await;
+    assert!(stderr.contains("No PKCS#8 or RSA private key"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+
+    let stderr = start_failure(&bridge, "tls_no_cert", &tls("bridge.key", "bridge.key")).await;
+    assert!(stderr.contains("No PEM certificates"), "{}", stderr);
+
+    let stderr = start_failure(&bridge, "tls_missing", &tls("missing.pem", "bridge.key")).await;
+    assert!(stderr.contains("Couldn't read TLS file"), "{}", stderr);
+
+    let config = format!("{}socket = \"hoo.sock\"\n", tls("bridge.pem", "bridge.key"));
+    let stderr = start_failure(&bridge, "tls_socket", &config).await;
+    assert!(stderr.contains("Can't serve TLS on the Unix socket"), "{}", stderr);
+}